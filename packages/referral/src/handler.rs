@@ -0,0 +1,98 @@
+use cosmwasm_std::{
+    to_binary, Addr, Binary, Deps, DepsMut, MessageInfo, Response, StdError, StdResult,
+};
+
+use crate::msg::{
+    AllRefResponse, AllReferredOfResponse, ExecuteMsg, QueryMsg, RefChainsResponse, RefOfResponse,
+};
+use crate::referral::{
+    DEFAULT_ALL_LIMIT, DEFAULT_DEPTH, DEFAULT_REFERRED_LIMIT, MAX_DEPTH, MAX_LIMIT,
+};
+use crate::store::{assert_valid_ref, ReferralStore};
+
+/// Handle [`ExecuteMsg`] against `store`, can be called directly from contract `execute` entry.
+pub fn handle_execute<S: ReferralStore>(
+    deps: DepsMut,
+    info: MessageInfo,
+    store: &S,
+    msg: ExecuteMsg,
+) -> StdResult<Response> {
+    match msg {
+        ExecuteMsg::SetRef { referrer } => {
+            let referrer = deps.api.addr_validate(&referrer)?;
+
+            // a referrer is set once, replacing it would move volume already credited upwards
+            if store.has_ref(deps.storage, &info.sender)? {
+                return Err(StdError::generic_err(format!(
+                    "{} already has a referrer",
+                    info.sender
+                )));
+            }
+            assert_valid_ref(store, deps.storage, &info.sender, &referrer)?;
+
+            store.set_ref(deps.storage, &info.sender, &referrer)?;
+
+            Ok(Response::new()
+                .add_attribute("action", "set_ref")
+                .add_attribute("referred", info.sender)
+                .add_attribute("referrer", referrer))
+        }
+    }
+}
+
+/// Handle [`QueryMsg`] against `store`, can be called directly from contract `query` entry.
+pub fn handle_query<S: ReferralStore>(deps: Deps, store: &S, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::RefOf { address } => {
+            let address = deps.api.addr_validate(&address)?;
+
+            to_binary(&RefOfResponse {
+                referrer: store.ref_of(deps.storage, &address)?,
+            })
+        }
+        QueryMsg::RefChains { address, depth } => {
+            let address = deps.api.addr_validate(&address)?;
+            let depth = depth.unwrap_or(DEFAULT_DEPTH).min(MAX_DEPTH);
+
+            to_binary(&RefChainsResponse {
+                chains: store.ref_chains(deps.storage, &address, Some(depth))?,
+            })
+        }
+        QueryMsg::AllRef {
+            start_after,
+            limit,
+            is_ascending,
+        } => {
+            let start_after = validate_optional(deps, start_after)?;
+            let limit = limit.unwrap_or(DEFAULT_ALL_LIMIT).min(MAX_LIMIT);
+
+            to_binary(&AllRefResponse {
+                refs: store.all_ref(deps.storage, start_after, Some(limit), is_ascending),
+            })
+        }
+        QueryMsg::AllReferredOf {
+            address,
+            start_after,
+            limit,
+            is_ascending,
+        } => {
+            let address = deps.api.addr_validate(&address)?;
+            let start_after = validate_optional(deps, start_after)?;
+            let limit = limit.unwrap_or(DEFAULT_REFERRED_LIMIT).min(MAX_LIMIT);
+
+            to_binary(&AllReferredOfResponse {
+                referred: store.all_referred_of(
+                    deps.storage,
+                    address,
+                    start_after,
+                    Some(limit),
+                    is_ascending,
+                )?,
+            })
+        }
+    }
+}
+
+fn validate_optional(deps: Deps, addr: Option<String>) -> StdResult<Option<Addr>> {
+    addr.map(|e| deps.api.addr_validate(&e)).transpose()
+}
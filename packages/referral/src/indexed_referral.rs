@@ -1,10 +1,12 @@
 use cosmwasm_std::{Addr, Order, StdError, StdResult, Storage};
-use cw_storage_plus::{Bound, Index, IndexList, IndexedMap, MultiIndex};
+use cw_storage_plus::{Bound, Index, IndexList};
+use tw_storage_extra::cow::{IndexedMapCow, MultiIndexCow};
 
-use crate::referral::{Refer, DEFAULT_ALL_LIMIT, DEFAULT_DEPTH, DEFAULT_REFERRED_LIMIT};
+use crate::referral::{Refer, DEFAULT_ALL_LIMIT, DEFAULT_REFERRED_LIMIT};
+use crate::store::ReferralStore;
 
 pub struct ReferralIndexes<'a> {
-    pub referred: MultiIndex<'a, (Addr, Vec<u8>), Refer>,
+    pub referred: MultiIndexCow<'a, (Addr, Vec<u8>), Refer>,
 }
 
 impl IndexList<Refer> for ReferralIndexes<'_> {
@@ -14,27 +16,77 @@ impl IndexList<Refer> for ReferralIndexes<'_> {
     }
 }
 
-pub struct IndexedReferral<'a>(IndexedMap<'a, &'a Addr, Refer, ReferralIndexes<'a>>);
+pub struct IndexedReferral<'a>(IndexedMapCow<'a, Addr, Refer, ReferralIndexes<'a>>);
 
 impl<'a> IndexedReferral<'a> {
-    pub fn new(ref_namespace: &'a str, ref_index_namespace: &'a str) -> Self {
-        IndexedReferral(IndexedMap::new(
+    pub const fn new(ref_namespace: &'a str, ref_index_namespace: &'a str) -> Self {
+        IndexedReferral(IndexedMapCow::new_ref(
             ref_namespace,
             ReferralIndexes {
-                referred: MultiIndex::new(
-                    |refer, key| (refer.referrer.clone(), key),
+                referred: MultiIndexCow::new_ref(
                     ref_namespace,
                     ref_index_namespace,
+                    |refer, key| (refer.referrer.clone(), key),
                 ),
             },
         ))
     }
 
+    // inherent forwards keep callers working without importing `ReferralStore`
     pub fn set_ref(
         &self,
         storage: &mut dyn Storage,
         referred_addr: &Addr,
         referrer_addr: &Addr,
+    ) -> StdResult<()> {
+        ReferralStore::set_ref(self, storage, referred_addr, referrer_addr)
+    }
+
+    pub fn ref_chains(
+        &self,
+        storage: &dyn Storage,
+        addr: &Addr,
+        depth: Option<u64>,
+    ) -> StdResult<Vec<Addr>> {
+        ReferralStore::ref_chains(self, storage, addr, depth)
+    }
+
+    pub fn ref_of(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<Option<Addr>> {
+        ReferralStore::ref_of(self, storage, addr)
+    }
+
+    pub fn has_ref(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<bool> {
+        ReferralStore::has_ref(self, storage, addr)
+    }
+
+    pub fn all_ref(
+        &self,
+        storage: &dyn Storage,
+        start_after: Option<Addr>,
+        limit: Option<u64>,
+        is_ascending: Option<bool>,
+    ) -> Vec<Refer> {
+        ReferralStore::all_ref(self, storage, start_after, limit, is_ascending)
+    }
+
+    pub fn all_referred_of(
+        &self,
+        storage: &dyn Storage,
+        addr: Addr,
+        start_after: Option<Addr>,
+        limit: Option<u64>,
+        is_ascending: Option<bool>,
+    ) -> StdResult<Vec<Addr>> {
+        ReferralStore::all_referred_of(self, storage, addr, start_after, limit, is_ascending)
+    }
+}
+
+impl ReferralStore for IndexedReferral<'_> {
+    fn set_ref(
+        &self,
+        storage: &mut dyn Storage,
+        referred_addr: &Addr,
+        referrer_addr: &Addr,
     ) -> StdResult<()> {
         if referred_addr == referrer_addr {
             return Err(StdError::generic_err(
@@ -44,7 +96,7 @@ impl<'a> IndexedReferral<'a> {
 
         self.0.save(
             storage,
-            referred_addr,
+            referred_addr.clone(),
             &Refer {
                 referrer: referrer_addr.clone(),
                 referred: referred_addr.clone(),
@@ -54,33 +106,11 @@ impl<'a> IndexedReferral<'a> {
         Ok(())
     }
 
-    pub fn ref_chains(
-        &self,
-        storage: &dyn Storage,
-        addr: &Addr,
-        depth: Option<u64>,
-    ) -> StdResult<Vec<Addr>> {
-        let mut chains: Vec<Addr> = vec![];
-
-        for _ in 0..depth.unwrap_or(DEFAULT_DEPTH) {
-            match self.0.may_load(storage, chains.last().unwrap_or(addr))? {
-                Some(r_addr) => chains.push(r_addr.referrer),
-                None => break,
-            };
-        }
-
-        Ok(chains)
-    }
-
-    pub fn ref_of(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<Option<Addr>> {
-        Ok(self.0.may_load(storage, addr)?.map(|r| r.referrer))
-    }
-
-    pub fn has_ref(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<bool> {
-        Ok(self.ref_of(storage, addr)?.is_some())
+    fn ref_of(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<Option<Addr>> {
+        Ok(self.0.may_load(storage, addr.clone())?.map(|r| r.referrer))
     }
 
-    pub fn all_ref(
+    fn all_ref(
         &self,
         storage: &dyn Storage,
         start_after: Option<Addr>,
@@ -105,13 +135,14 @@ impl<'a> IndexedReferral<'a> {
         };
 
         self.0
+            .prefix(())
             .range(storage, bound.0, bound.1, bound.2)
             .map(|e| e.unwrap().1)
             .take(limit.unwrap_or(DEFAULT_ALL_LIMIT) as usize)
             .collect::<Vec<_>>()
     }
 
-    pub fn all_referred_of(
+    fn all_referred_of(
         &self,
         storage: &dyn Storage,
         addr: Addr,
@@ -136,14 +167,12 @@ impl<'a> IndexedReferral<'a> {
             ),
         };
 
-        Ok(self
-            .0
-            .idx
+        self.0
+            .index
             .referred
-            .prefix(addr)
+            .prefix_de::<Addr>(addr)
             .keys(storage, bound.0, bound.1, bound.2)
-            .map(|e| Addr::unchecked(String::from_utf8(e).unwrap()))
             .take(limit.unwrap_or(DEFAULT_REFERRED_LIMIT) as usize)
-            .collect::<Vec<_>>())
+            .collect()
     }
 }
@@ -1,10 +1,17 @@
+mod handler;
 mod indexed_referral;
+pub mod msg;
 mod referral;
 mod single_sided_referral;
+mod store;
+mod tier;
 
+pub use handler::{handle_execute, handle_query};
 pub use indexed_referral::IndexedReferral;
-pub use referral::Refer;
+pub use referral::{ImportPolicy, Refer};
 pub use single_sided_referral::SingleSidedReferral;
+pub use store::{migrate_referral, ReferralStore};
+pub use tier::{ReferrerStat, Tier, TierEngine};
 
 #[cfg(test)]
 mod test;
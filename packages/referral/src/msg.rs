@@ -0,0 +1,54 @@
+use cosmwasm_std::Addr;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::referral::Refer;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Set referrer of the message sender
+    SetRef { referrer: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// Referrer of a specific address, returns [`RefOfResponse`]
+    RefOf { address: String },
+    /// Referral chain of a specific address, returns [`RefChainsResponse`]
+    RefChains { address: String, depth: Option<u64> },
+    /// All referral relationships, returns [`AllRefResponse`]
+    AllRef {
+        start_after: Option<String>,
+        limit: Option<u64>,
+        is_ascending: Option<bool>,
+    },
+    /// All addresses referred by a specific address, returns [`AllReferredOfResponse`]
+    AllReferredOf {
+        address: String,
+        start_after: Option<String>,
+        limit: Option<u64>,
+        is_ascending: Option<bool>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RefOfResponse {
+    pub referrer: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RefChainsResponse {
+    pub chains: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllRefResponse {
+    pub refs: Vec<Refer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllReferredOfResponse {
+    pub referred: Vec<Addr>,
+}
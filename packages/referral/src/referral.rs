@@ -5,9 +5,25 @@ use serde::{Deserialize, Serialize};
 pub const DEFAULT_DEPTH: u64 = 3;
 pub const DEFAULT_REFERRED_LIMIT: u64 = 50;
 pub const DEFAULT_ALL_LIMIT: u64 = 100;
+pub const DEFAULT_LEADERBOARD_LIMIT: u64 = 50;
+pub const MAX_EXPORT_LIMIT: u64 = 500;
+pub const MAX_LIMIT: u64 = 300;
+pub const MAX_DEPTH: u64 = 10;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Refer {
     pub referrer: Addr,
     pub referred: Addr,
 }
+
+/// How to handle an imported referral of an address that already has a different referrer.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportPolicy {
+    /// Keep existing referrer
+    Skip,
+    /// Replace existing referrer
+    Overwrite,
+    /// Fail the whole import
+    Reject,
+}
@@ -1,7 +1,8 @@
 use cosmwasm_std::{Addr, Order, StdError, StdResult, Storage};
 use cw_storage_plus::{Bound, Map};
 
-use crate::referral::{Refer, DEFAULT_ALL_LIMIT, DEFAULT_DEPTH};
+use crate::referral::{Refer, DEFAULT_ALL_LIMIT};
+use crate::store::ReferralStore;
 
 pub struct SingleSidedReferral<'a>(Map<'a, &'a Addr, Addr>);
 
@@ -10,21 +11,14 @@ impl<'a> SingleSidedReferral<'a> {
         SingleSidedReferral(Map::new(map_namespace))
     }
 
+    // inherent forwards keep callers working without importing `ReferralStore`
     pub fn set_ref(
         &self,
         storage: &mut dyn Storage,
         referred_addr: &Addr,
         referrer_addr: &Addr,
     ) -> StdResult<()> {
-        if referred_addr == referrer_addr {
-            return Err(StdError::generic_err(
-                "Referrer can not be same address as referral",
-            ));
-        }
-
-        self.0.save(storage, referred_addr, referrer_addr)?;
-
-        Ok(())
+        ReferralStore::set_ref(self, storage, referred_addr, referrer_addr)
     }
 
     pub fn ref_chains(
@@ -33,25 +27,15 @@ impl<'a> SingleSidedReferral<'a> {
         addr: &Addr,
         depth: Option<u64>,
     ) -> StdResult<Vec<Addr>> {
-        let mut chains: Vec<Addr> = vec![];
-
-        for _ in 0..depth.unwrap_or(DEFAULT_DEPTH) {
-            if let Some(r_addr) = self.0.may_load(storage, chains.last().unwrap_or(addr))? {
-                chains.push(r_addr);
-            } else {
-                break;
-            }
-        }
-
-        Ok(chains)
+        ReferralStore::ref_chains(self, storage, addr, depth)
     }
 
     pub fn ref_of(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<Option<Addr>> {
-        self.0.may_load(storage, addr)
+        ReferralStore::ref_of(self, storage, addr)
     }
 
     pub fn has_ref(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<bool> {
-        Ok(self.ref_of(storage, addr)?.is_some())
+        ReferralStore::has_ref(self, storage, addr)
     }
 
     pub fn all_ref(
@@ -60,6 +44,39 @@ impl<'a> SingleSidedReferral<'a> {
         start_after: Option<Addr>,
         limit: Option<u64>,
         is_ascending: Option<bool>,
+    ) -> Vec<Refer> {
+        ReferralStore::all_ref(self, storage, start_after, limit, is_ascending)
+    }
+}
+
+impl ReferralStore for SingleSidedReferral<'_> {
+    fn set_ref(
+        &self,
+        storage: &mut dyn Storage,
+        referred_addr: &Addr,
+        referrer_addr: &Addr,
+    ) -> StdResult<()> {
+        if referred_addr == referrer_addr {
+            return Err(StdError::generic_err(
+                "Referrer can not be same address as referral",
+            ));
+        }
+
+        self.0.save(storage, referred_addr, referrer_addr)?;
+
+        Ok(())
+    }
+
+    fn ref_of(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<Option<Addr>> {
+        self.0.may_load(storage, addr)
+    }
+
+    fn all_ref(
+        &self,
+        storage: &dyn Storage,
+        start_after: Option<Addr>,
+        limit: Option<u64>,
+        is_ascending: Option<bool>,
     ) -> Vec<Refer> {
         let bound = match is_ascending.unwrap_or(true) {
             true => (
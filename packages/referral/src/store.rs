@@ -0,0 +1,190 @@
+use cosmwasm_std::{Addr, StdError, StdResult, Storage};
+use std::collections::HashSet;
+
+use crate::referral::{ImportPolicy, Refer, DEFAULT_ALL_LIMIT, DEFAULT_DEPTH, MAX_EXPORT_LIMIT};
+
+/// Common interface of referral storages, so contracts can be generic over the backend.
+///
+/// Only the storage specific accessors are required, chain walk and existence check are
+/// derived from `ref_of`.
+pub trait ReferralStore {
+    /// Set `referrer_addr` as referrer of `referred_addr`.
+    fn set_ref(
+        &self,
+        storage: &mut dyn Storage,
+        referred_addr: &Addr,
+        referrer_addr: &Addr,
+    ) -> StdResult<()>;
+
+    /// Get a referrer of a specific address.
+    fn ref_of(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<Option<Addr>>;
+
+    /// Get all referral relationships, ordered by referred address.
+    fn all_ref(
+        &self,
+        storage: &dyn Storage,
+        start_after: Option<Addr>,
+        limit: Option<u64>,
+        is_ascending: Option<bool>,
+    ) -> Vec<Refer>;
+
+    /// Get a referral chain of a specific address. Default depth is 3.
+    fn ref_chains(
+        &self,
+        storage: &dyn Storage,
+        addr: &Addr,
+        depth: Option<u64>,
+    ) -> StdResult<Vec<Addr>> {
+        let mut chains: Vec<Addr> = vec![];
+
+        for _ in 0..depth.unwrap_or(DEFAULT_DEPTH) {
+            match self.ref_of(storage, chains.last().unwrap_or(addr))? {
+                Some(r_addr) => chains.push(r_addr),
+                None => break,
+            };
+        }
+
+        Ok(chains)
+    }
+
+    /// Get a boolean that state a specific address has a referrer or not.
+    fn has_ref(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<bool> {
+        Ok(self.ref_of(storage, addr)?.is_some())
+    }
+
+    /// Get all addresses referred by a specific address.
+    ///
+    /// Requires a referred address index, stores without one return an error.
+    fn all_referred_of(
+        &self,
+        _storage: &dyn Storage,
+        _addr: Addr,
+        _start_after: Option<Addr>,
+        _limit: Option<u64>,
+        _is_ascending: Option<bool>,
+    ) -> StdResult<Vec<Addr>> {
+        Err(StdError::generic_err(
+            "Referral store does not support querying referred addresses",
+        ))
+    }
+
+    /// Import referral relationships in order, validating every one of them against existing
+    /// and previously imported relationships.
+    ///
+    /// Already stored relationships are skipped, so a large import can be split over several
+    /// transactions and a partially applied batch can be sent again. Returns number of written
+    /// relationships.
+    fn import(
+        &self,
+        storage: &mut dyn Storage,
+        refs: Vec<Refer>,
+        policy: ImportPolicy,
+    ) -> StdResult<u64> {
+        let mut written = 0u64;
+
+        for refer in refs {
+            assert_valid_ref(self, storage, &refer.referred, &refer.referrer)?;
+
+            match self.ref_of(storage, &refer.referred)? {
+                Some(existing) if existing == refer.referrer => continue,
+                Some(_) if policy == ImportPolicy::Skip => continue,
+                Some(existing) if policy == ImportPolicy::Reject => {
+                    return Err(StdError::generic_err(format!(
+                        "{} already referred by {}",
+                        refer.referred, existing
+                    )));
+                }
+                _ => {}
+            }
+
+            self.set_ref(storage, &refer.referred, &refer.referrer)?;
+            written += 1;
+        }
+
+        Ok(written)
+    }
+
+    /// Get a batch of referral relationships in ascending order of referred address, capped at
+    /// `MAX_EXPORT_LIMIT` entries. Continue with the last referred address as `start_after`.
+    fn export(
+        &self,
+        storage: &dyn Storage,
+        start_after: Option<Addr>,
+        limit: Option<u64>,
+    ) -> Vec<Refer> {
+        let limit = limit.unwrap_or(DEFAULT_ALL_LIMIT).min(MAX_EXPORT_LIMIT);
+
+        self.all_ref(storage, start_after, Some(limit), Some(true))
+    }
+}
+
+/// Reject self-referral and a referral of `referred` by `referrer` closing a cycle, which would
+/// credit referral rewards around the loop.
+pub(crate) fn assert_valid_ref<S: ReferralStore + ?Sized>(
+    store: &S,
+    storage: &dyn Storage,
+    referred: &Addr,
+    referrer: &Addr,
+) -> StdResult<()> {
+    if referred == referrer {
+        return Err(StdError::generic_err(format!(
+            "Referrer can not be same address as referral: {}",
+            referred
+        )));
+    }
+
+    // walk up from new referrer, reaching referred means the relationship closes a cycle
+    let mut visited = HashSet::new();
+    let mut cursor = Some(referrer.clone());
+    while let Some(addr) = cursor {
+        if &addr == referred {
+            return Err(StdError::generic_err(format!(
+                "Referral of {} by {} creates a cycle",
+                referred, referrer
+            )));
+        }
+        if !visited.insert(addr.clone()) {
+            break;
+        }
+        cursor = store.ref_of(storage, &addr)?;
+    }
+
+    Ok(())
+}
+
+/// Copy at most `limit` referral relationships from `from` into `to`, starting after
+/// `start_after` referred address.
+///
+/// Returns the last copied referred address if there might be more left, so migration can be
+/// continued in another transaction, or `None` once everything is copied. A zero `limit` is
+/// rejected, as it would never make progress.
+pub fn migrate_referral<F, T>(
+    storage: &mut dyn Storage,
+    from: &F,
+    to: &T,
+    start_after: Option<Addr>,
+    limit: Option<u64>,
+) -> StdResult<Option<Addr>>
+where
+    F: ReferralStore,
+    T: ReferralStore,
+{
+    if limit == Some(0) {
+        return Err(StdError::generic_err(
+            "Migration limit must be greater than zero",
+        ));
+    }
+
+    let refs = from.all_ref(storage, start_after, limit, Some(true));
+
+    for refer in refs.iter() {
+        to.set_ref(storage, &refer.referred, &refer.referrer)?;
+    }
+
+    Ok(match refs.last() {
+        Some(last) if refs.len() as u64 == limit.unwrap_or(DEFAULT_ALL_LIMIT) => {
+            Some(last.referred.clone())
+        }
+        _ => None,
+    })
+}
@@ -1,8 +1,19 @@
 #[cfg(test)]
 mod tests {
-    use cosmwasm_std::{testing::mock_dependencies, Addr};
-
-    use crate::{IndexedReferral, SingleSidedReferral};
+    use cosmwasm_std::{
+        from_binary,
+        testing::{mock_dependencies, mock_info},
+        Addr, Storage, Uint128,
+    };
+
+    use crate::msg::{
+        AllRefResponse, AllReferredOfResponse, ExecuteMsg, QueryMsg, RefChainsResponse,
+        RefOfResponse,
+    };
+    use crate::{
+        handle_execute, handle_query, migrate_referral, ImportPolicy, IndexedReferral, Refer,
+        ReferralStore, SingleSidedReferral, Tier, TierEngine,
+    };
 
     #[test]
     fn test_single_sided_referral() {
@@ -112,4 +123,617 @@ mod tests {
 
         assert_eq!(all_ref_a, vec![h.clone(), bb.clone(), b.clone()]);
     }
+
+    fn chain_of<S: ReferralStore>(store: &S, storage: &dyn Storage, addr: &Addr) -> Vec<Addr> {
+        store.ref_chains(storage, addr, None).unwrap()
+    }
+
+    #[test]
+    fn test_generic_referral_store() {
+        let mut deps = mock_dependencies(&[]);
+        let single: SingleSidedReferral = SingleSidedReferral::new("single");
+        let indexed: IndexedReferral = IndexedReferral::new("indexed", "indexed_idx");
+
+        let a = Addr::unchecked("a");
+        let b = Addr::unchecked("b");
+        let c = Addr::unchecked("c");
+
+        single.set_ref(&mut deps.storage, &b, &a).unwrap();
+        single.set_ref(&mut deps.storage, &c, &b).unwrap();
+        indexed.set_ref(&mut deps.storage, &b, &a).unwrap();
+        indexed.set_ref(&mut deps.storage, &c, &b).unwrap();
+
+        assert_eq!(
+            chain_of(&single, &deps.storage, &c),
+            vec![b.clone(), a.clone()]
+        );
+        assert_eq!(
+            chain_of(&single, &deps.storage, &c),
+            chain_of(&indexed, &deps.storage, &c)
+        );
+        assert!(!single.has_ref(&deps.storage, &a).unwrap());
+        assert!(indexed.has_ref(&deps.storage, &b).unwrap());
+    }
+
+    #[test]
+    fn test_migrate_referral() {
+        let mut deps = mock_dependencies(&[]);
+        let single: SingleSidedReferral = SingleSidedReferral::new("single");
+        let indexed: IndexedReferral = IndexedReferral::new("indexed", "indexed_idx");
+
+        let a = Addr::unchecked("a");
+        let b = Addr::unchecked("b");
+        let c = Addr::unchecked("c");
+        let d = Addr::unchecked("d");
+
+        single.set_ref(&mut deps.storage, &b, &a).unwrap();
+        single.set_ref(&mut deps.storage, &c, &a).unwrap();
+        single.set_ref(&mut deps.storage, &d, &c).unwrap();
+
+        let next = migrate_referral(&mut deps.storage, &single, &indexed, None, Some(2)).unwrap();
+        assert_eq!(next, Some(c.clone()));
+        assert!(!indexed.has_ref(&deps.storage, &d).unwrap());
+
+        // zero limit would never make progress
+        migrate_referral(&mut deps.storage, &single, &indexed, next.clone(), Some(0)).unwrap_err();
+
+        let next = migrate_referral(&mut deps.storage, &single, &indexed, next, Some(2)).unwrap();
+        assert_eq!(next, None);
+
+        assert_eq!(
+            single.all_ref(&deps.storage, None, None, None),
+            indexed.all_ref(&deps.storage, None, None, None)
+        );
+        assert_eq!(
+            indexed
+                .all_referred_of(&deps.storage, a, None, None, None)
+                .unwrap(),
+            vec![b, c.clone()]
+        );
+        assert_eq!(
+            indexed.ref_chains(&deps.storage, &d, None).unwrap(),
+            vec![c, Addr::unchecked("a")]
+        );
+    }
+
+    #[test]
+    fn test_handle_execute_query() {
+        let mut deps = mock_dependencies(&[]);
+        let referral: IndexedReferral = IndexedReferral::new("ref_pk", "ref_idx");
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let carol = Addr::unchecked("carol");
+
+        for (sender, referrer) in [(&bob, &alice), (&carol, &bob)] {
+            handle_execute(
+                deps.as_mut(),
+                mock_info(sender.as_str(), &[]),
+                &referral,
+                ExecuteMsg::SetRef {
+                    referrer: referrer.to_string(),
+                },
+            )
+            .unwrap();
+        }
+
+        let set_ref = |deps: cosmwasm_std::DepsMut, sender: &Addr, referrer: &Addr| {
+            handle_execute(
+                deps,
+                mock_info(sender.as_str(), &[]),
+                &referral,
+                ExecuteMsg::SetRef {
+                    referrer: referrer.to_string(),
+                },
+            )
+        };
+
+        // self-referral
+        set_ref(deps.as_mut(), &alice, &alice).unwrap_err();
+        // referrer can not be replaced
+        set_ref(deps.as_mut(), &carol, &alice).unwrap_err();
+        // alice -> carol -> bob -> alice
+        let err = set_ref(deps.as_mut(), &alice, &carol).unwrap_err();
+        assert!(err.to_string().contains("creates a cycle"));
+        assert!(!referral.has_ref(&deps.storage, &alice).unwrap());
+
+        let res: RefOfResponse = from_binary(
+            &handle_query(
+                deps.as_ref(),
+                &referral,
+                QueryMsg::RefOf {
+                    address: carol.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.referrer, Some(bob.clone()));
+
+        let res: RefChainsResponse = from_binary(
+            &handle_query(
+                deps.as_ref(),
+                &referral,
+                QueryMsg::RefChains {
+                    address: carol.to_string(),
+                    depth: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.chains, vec![bob.clone(), alice.clone()]);
+
+        let res: AllRefResponse = from_binary(
+            &handle_query(
+                deps.as_ref(),
+                &referral,
+                QueryMsg::AllRef {
+                    start_after: Some(bob.to_string()),
+                    limit: None,
+                    is_ascending: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.refs.len(), 1);
+        assert_eq!(res.refs[0].referred, carol);
+
+        let res: AllReferredOfResponse = from_binary(
+            &handle_query(
+                deps.as_ref(),
+                &referral,
+                QueryMsg::AllReferredOf {
+                    address: alice.to_string(),
+                    start_after: None,
+                    limit: None,
+                    is_ascending: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.referred, vec![bob]);
+
+        // single sided referral has no referred index
+        handle_query(
+            deps.as_ref(),
+            &SingleSidedReferral::new("single"),
+            QueryMsg::AllReferredOf {
+                address: alice.to_string(),
+                start_after: None,
+                limit: None,
+                is_ascending: None,
+            },
+        )
+        .unwrap_err();
+
+        // limits and depth are capped
+        let mut referred = carol.clone();
+        for i in 0..400 {
+            let referrer = Addr::unchecked(format!("addr{:03}", i));
+            referral
+                .set_ref(&mut deps.storage, &referred, &referrer)
+                .unwrap();
+            referral
+                .set_ref(
+                    &mut deps.storage,
+                    &Addr::unchecked(format!("ref{:03}", i)),
+                    &alice,
+                )
+                .unwrap();
+            referred = referrer;
+        }
+        let res: RefChainsResponse = from_binary(
+            &handle_query(
+                deps.as_ref(),
+                &referral,
+                QueryMsg::RefChains {
+                    address: carol.to_string(),
+                    depth: Some(u64::MAX),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.chains.len(), 10);
+        let res: AllRefResponse = from_binary(
+            &handle_query(
+                deps.as_ref(),
+                &referral,
+                QueryMsg::AllRef {
+                    start_after: None,
+                    limit: Some(u64::MAX),
+                    is_ascending: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.refs.len(), 300);
+        let res: AllReferredOfResponse = from_binary(
+            &handle_query(
+                deps.as_ref(),
+                &referral,
+                QueryMsg::AllReferredOf {
+                    address: alice.to_string(),
+                    start_after: None,
+                    limit: Some(u64::MAX),
+                    is_ascending: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.referred.len(), 300);
+    }
+
+    #[test]
+    fn test_const_indexed_referral_keeps_layout() {
+        use cw_storage_plus::{Index, IndexList, IndexedMap, MultiIndex};
+
+        struct LegacyIndexes<'a> {
+            referred: MultiIndex<'a, (Addr, Vec<u8>), Refer>,
+        }
+
+        impl IndexList<Refer> for LegacyIndexes<'_> {
+            fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Refer>> + '_> {
+                let v: Vec<&dyn Index<Refer>> = vec![&self.referred];
+                Box::new(v.into_iter())
+            }
+        }
+
+        const REFERRAL: IndexedReferral = IndexedReferral::new("ref_pk", "ref_idx");
+
+        let mut deps = mock_dependencies(&[]);
+        let legacy: IndexedMap<&Addr, Refer, LegacyIndexes> = IndexedMap::new(
+            "ref_pk",
+            LegacyIndexes {
+                referred: MultiIndex::new(
+                    |refer, key| (refer.referrer.clone(), key),
+                    "ref_pk",
+                    "ref_idx",
+                ),
+            },
+        );
+
+        let a = Addr::unchecked("a");
+        let b = Addr::unchecked("b");
+        let c = Addr::unchecked("c");
+
+        legacy
+            .save(
+                &mut deps.storage,
+                &b,
+                &Refer {
+                    referrer: a.clone(),
+                    referred: b.clone(),
+                },
+            )
+            .unwrap();
+        REFERRAL.set_ref(&mut deps.storage, &c, &a).unwrap();
+
+        assert_eq!(REFERRAL.ref_of(&deps.storage, &b).unwrap(), Some(a.clone()));
+        assert_eq!(
+            REFERRAL
+                .all_referred_of(&deps.storage, a.clone(), None, None, None)
+                .unwrap(),
+            vec![b, c.clone()]
+        );
+        assert_eq!(
+            legacy
+                .idx
+                .referred
+                .prefix(a.clone())
+                .keys(&deps.storage, None, None, cosmwasm_std::Order::Ascending)
+                .count(),
+            2
+        );
+        assert_eq!(legacy.load(&deps.storage, &c).unwrap().referrer, a);
+    }
+
+    #[test]
+    fn test_tier_engine() {
+        let mut deps = mock_dependencies(&[]);
+        let referral: SingleSidedReferral = SingleSidedReferral::new("ref");
+        const TIER: TierEngine = TierEngine::new("stat", "stat__volume", "active", "tier");
+
+        let a = Addr::unchecked("a");
+        let b = Addr::unchecked("b");
+        let c = Addr::unchecked("c");
+        let d = Addr::unchecked("d");
+
+        referral.set_ref(&mut deps.storage, &b, &a).unwrap();
+        referral.set_ref(&mut deps.storage, &c, &a).unwrap();
+        referral.set_ref(&mut deps.storage, &d, &c).unwrap();
+
+        let bronze = Tier {
+            name: "bronze".to_string(),
+            min_active_referees: 1,
+            min_volume: Uint128::from(100u64),
+        };
+        let silver = Tier {
+            name: "silver".to_string(),
+            min_active_referees: 2,
+            min_volume: Uint128::from(1000u64),
+        };
+
+        TIER.set_tiers(&mut deps.storage, vec![silver.clone(), bronze.clone()])
+            .unwrap_err();
+        TIER.set_tiers(&mut deps.storage, vec![bronze.clone(), silver.clone()])
+            .unwrap();
+
+        // a has no referrer, nothing to credit
+        let credited = TIER
+            .record_volume(
+                &mut deps.storage,
+                &referral,
+                &a,
+                Uint128::from(500u64),
+                None,
+            )
+            .unwrap();
+        assert!(credited.is_empty());
+
+        TIER.record_volume(
+            &mut deps.storage,
+            &referral,
+            &d,
+            Uint128::from(300u64),
+            None,
+        )
+        .unwrap();
+        TIER.record_volume(
+            &mut deps.storage,
+            &referral,
+            &d,
+            Uint128::from(300u64),
+            None,
+        )
+        .unwrap();
+
+        let stat = TIER.stat_of(&deps.storage, &c).unwrap();
+        assert_eq!(stat.active_referees, 1);
+        assert_eq!(stat.volume, Uint128::from(600u64));
+
+        // d is active referee of c only, a gets volume from the whole downline
+        let stat = TIER.stat_of(&deps.storage, &a).unwrap();
+        assert_eq!(stat.active_referees, 0);
+        assert_eq!(stat.volume, Uint128::from(600u64));
+
+        assert_eq!(TIER.tier_of(&deps.storage, &a).unwrap(), None);
+        assert_eq!(
+            TIER.tier_of(&deps.storage, &c).unwrap(),
+            Some(bronze.clone())
+        );
+
+        // zero volume does not make b active
+        let credited = TIER
+            .record_volume(&mut deps.storage, &referral, &b, Uint128::zero(), None)
+            .unwrap();
+        assert!(credited.is_empty());
+        assert_eq!(TIER.stat_of(&deps.storage, &a).unwrap().active_referees, 0);
+
+        TIER.record_volume(
+            &mut deps.storage,
+            &referral,
+            &b,
+            Uint128::from(200u64),
+            None,
+        )
+        .unwrap();
+        TIER.record_volume(
+            &mut deps.storage,
+            &referral,
+            &c,
+            Uint128::from(200u64),
+            None,
+        )
+        .unwrap();
+
+        let stat = TIER.stat_of(&deps.storage, &a).unwrap();
+        assert_eq!(stat.active_referees, 2);
+        assert_eq!(stat.volume, Uint128::from(1000u64));
+        assert_eq!(TIER.tier_of(&deps.storage, &a).unwrap(), Some(silver));
+
+        let leaderboard = TIER
+            .leaderboard(&deps.storage, None, None)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.address, e.volume.u128()))
+            .collect::<Vec<_>>();
+        assert_eq!(leaderboard, vec![(a.clone(), 1000), (c.clone(), 600)]);
+
+        let leaderboard = TIER
+            .leaderboard(&deps.storage, Some(a), Some(1))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.address)
+            .collect::<Vec<_>>();
+        assert_eq!(leaderboard, vec![c]);
+    }
+
+    #[test]
+    fn test_leaderboard_ties() {
+        let mut deps = mock_dependencies(&[]);
+        let referral: SingleSidedReferral = SingleSidedReferral::new("ref");
+        const TIER: TierEngine = TierEngine::new("stat", "stat__volume", "active", "tier");
+
+        for (referred, referrer, volume) in [
+            ("a1", "a", 100u64),
+            ("b1", "b", 100),
+            ("c1", "c", 300),
+            ("d1", "d", 100),
+        ] {
+            let referred = Addr::unchecked(referred);
+            referral
+                .set_ref(&mut deps.storage, &referred, &Addr::unchecked(referrer))
+                .unwrap();
+            TIER.record_volume(
+                &mut deps.storage,
+                &referral,
+                &referred,
+                Uint128::from(volume),
+                None,
+            )
+            .unwrap();
+        }
+
+        // one page at a time through the tie, nothing skipped or repeated
+        let mut pages = vec![];
+        let mut start_after = None;
+        loop {
+            let page = TIER
+                .leaderboard(&deps.storage, start_after, Some(1))
+                .unwrap();
+            match page.last() {
+                Some(stat) => start_after = Some(stat.address.clone()),
+                None => break,
+            }
+            pages.extend(page.into_iter().map(|e| e.address.to_string()));
+        }
+        assert_eq!(pages, vec!["c", "d", "b", "a"]);
+
+        let page = TIER
+            .leaderboard(&deps.storage, Some(Addr::unchecked("d")), Some(5))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.address.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(page, vec!["b", "a"]);
+    }
+
+    fn refer(referred: &str, referrer: &str) -> Refer {
+        Refer {
+            referrer: Addr::unchecked(referrer),
+            referred: Addr::unchecked(referred),
+        }
+    }
+
+    fn import_export<S: ReferralStore>(referral: &S) {
+        let mut deps = mock_dependencies(&[]);
+
+        let refs = vec![refer("b", "a"), refer("c", "b"), refer("d", "c")];
+
+        assert_eq!(
+            referral
+                .import(&mut deps.storage, refs.clone(), ImportPolicy::Reject)
+                .unwrap(),
+            3
+        );
+
+        // resending a partially applied batch is a no-op for stored relationships
+        assert_eq!(
+            referral
+                .import(
+                    &mut deps.storage,
+                    vec![refer("d", "c"), refer("e", "d")],
+                    ImportPolicy::Reject
+                )
+                .unwrap(),
+            1
+        );
+
+        referral
+            .import(
+                &mut deps.storage,
+                vec![refer("f", "f")],
+                ImportPolicy::Overwrite,
+            )
+            .unwrap_err();
+        referral
+            .import(
+                &mut deps.storage,
+                vec![refer("a", "e")],
+                ImportPolicy::Overwrite,
+            )
+            .unwrap_err();
+        referral
+            .import(
+                &mut deps.storage,
+                vec![refer("c", "a")],
+                ImportPolicy::Reject,
+            )
+            .unwrap_err();
+
+        assert_eq!(
+            referral
+                .import(&mut deps.storage, vec![refer("c", "a")], ImportPolicy::Skip)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            referral
+                .ref_of(&deps.storage, &Addr::unchecked("c"))
+                .unwrap(),
+            Some(Addr::unchecked("b"))
+        );
+
+        assert_eq!(
+            referral
+                .import(
+                    &mut deps.storage,
+                    vec![refer("c", "a")],
+                    ImportPolicy::Overwrite
+                )
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            referral
+                .ref_of(&deps.storage, &Addr::unchecked("c"))
+                .unwrap(),
+            Some(Addr::unchecked("a"))
+        );
+
+        let first = referral.export(&deps.storage, None, Some(2));
+        assert_eq!(first, vec![refer("b", "a"), refer("c", "a")]);
+
+        let rest = referral.export(&deps.storage, Some(first[1].referred.clone()), None);
+        assert_eq!(rest, vec![refer("d", "c"), refer("e", "d")]);
+    }
+
+    #[test]
+    fn test_import_export() {
+        import_export(&SingleSidedReferral::new("ref_pk"));
+        import_export(&IndexedReferral::new("ref_pk", "ref_idx"));
+    }
+
+    mod inherent {
+        use cosmwasm_std::{testing::mock_dependencies, Addr};
+
+        // `ReferralStore` is deliberately not imported
+        use crate::{IndexedReferral, SingleSidedReferral};
+
+        #[test]
+        fn methods_without_trait_import() {
+            let mut deps = mock_dependencies(&[]);
+            let single: SingleSidedReferral = SingleSidedReferral::new("single");
+            let indexed: IndexedReferral = IndexedReferral::new("indexed", "indexed_idx");
+
+            let a = Addr::unchecked("a");
+            let b = Addr::unchecked("b");
+
+            single.set_ref(&mut deps.storage, &b, &a).unwrap();
+            indexed.set_ref(&mut deps.storage, &b, &a).unwrap();
+
+            assert_eq!(single.ref_of(&deps.storage, &b).unwrap(), Some(a.clone()));
+            assert!(indexed.has_ref(&deps.storage, &b).unwrap());
+            assert_eq!(
+                single.ref_chains(&deps.storage, &b, None).unwrap(),
+                indexed.ref_chains(&deps.storage, &b, None).unwrap()
+            );
+            assert_eq!(
+                single.all_ref(&deps.storage, None, None, None),
+                indexed.all_ref(&deps.storage, None, None, None)
+            );
+            assert_eq!(
+                indexed
+                    .all_referred_of(&deps.storage, a, None, None, None)
+                    .unwrap(),
+                vec![b]
+            );
+        }
+    }
 }
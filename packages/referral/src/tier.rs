@@ -0,0 +1,182 @@
+use cosmwasm_std::{Addr, Order, StdError, StdResult, Storage, Uint128};
+use cw_storage_plus::{Bound, Index, IndexList, PrimaryKey, U128Key};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tw_storage_extra::cow::{IndexedMapCow, ItemCow, MapCow, MultiIndexCow};
+
+use crate::referral::DEFAULT_LEADERBOARD_LIMIT;
+use crate::store::ReferralStore;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Tier {
+    pub name: String,
+    /// Minimum number of direct referees that have generated volume
+    pub min_active_referees: u64,
+    /// Minimum volume generated by the whole downline
+    pub min_volume: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferrerStat {
+    pub address: Addr,
+    pub active_referees: u64,
+    pub volume: Uint128,
+}
+
+impl ReferrerStat {
+    fn empty(address: Addr) -> Self {
+        Self {
+            address,
+            active_referees: 0,
+            volume: Uint128::zero(),
+        }
+    }
+}
+
+pub struct ReferrerStatIndexes<'a> {
+    pub volume: MultiIndexCow<'a, (U128Key, Vec<u8>), ReferrerStat>,
+}
+
+impl IndexList<ReferrerStat> for ReferrerStatIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<ReferrerStat>> + '_> {
+        let v: Vec<&dyn Index<ReferrerStat>> = vec![&self.volume];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Rank referrers by size and volume of their downline.
+///
+/// Volume is credited to every referrer in the referral chain of a trading address, while an
+/// address counts as active referee of its direct referrer once it has generated any volume.
+pub struct TierEngine<'a> {
+    stats: IndexedMapCow<'a, Addr, ReferrerStat, ReferrerStatIndexes<'a>>,
+    active: MapCow<'a, Addr, bool>,
+    tiers: ItemCow<'a, Vec<Tier>>,
+}
+
+impl<'a> TierEngine<'a> {
+    pub const fn new(
+        stat_namespace: &'a str,
+        stat_volume_namespace: &'a str,
+        active_namespace: &'a str,
+        tier_namespace: &'a str,
+    ) -> Self {
+        TierEngine {
+            stats: IndexedMapCow::new_ref(
+                stat_namespace,
+                ReferrerStatIndexes {
+                    volume: MultiIndexCow::new_ref(
+                        stat_namespace,
+                        stat_volume_namespace,
+                        |stat, key| (stat.volume.u128().into(), key),
+                    ),
+                },
+            ),
+            active: MapCow::new_ref(active_namespace),
+            tiers: ItemCow::new_ref(tier_namespace),
+        }
+    }
+
+    /// Set tier thresholds, ordered from the lowest to the highest tier.
+    pub fn set_tiers(&self, storage: &mut dyn Storage, tiers: Vec<Tier>) -> StdResult<()> {
+        for pair in tiers.windows(2) {
+            if pair[1].min_active_referees < pair[0].min_active_referees
+                || pair[1].min_volume < pair[0].min_volume
+            {
+                return Err(StdError::generic_err(format!(
+                    "Tier {} thresholds must not be lower than tier {}",
+                    pair[1].name, pair[0].name
+                )));
+            }
+        }
+
+        self.tiers.save(storage, &tiers)
+    }
+
+    pub fn tiers(&self, storage: &dyn Storage) -> StdResult<Vec<Tier>> {
+        Ok(self.tiers.may_load(storage)?.unwrap_or_default())
+    }
+
+    /// Credit `amount` generated by `referred` to its referral chain, up to `depth` referrers.
+    ///
+    /// Returns credited referrers, none for a zero `amount` which does not make `referred` active.
+    pub fn record_volume<S: ReferralStore>(
+        &self,
+        storage: &mut dyn Storage,
+        referral: &S,
+        referred: &Addr,
+        amount: Uint128,
+        depth: Option<u64>,
+    ) -> StdResult<Vec<Addr>> {
+        if amount.is_zero() {
+            return Ok(vec![]);
+        }
+
+        let chains = referral.ref_chains(storage, referred, depth)?;
+
+        let newly_active = match chains.first() {
+            Some(_) if !self.active.has(storage, referred.clone()) => {
+                self.active.save(storage, referred.clone(), &true)?;
+                true
+            }
+            _ => false,
+        };
+
+        for (i, referrer) in chains.iter().enumerate() {
+            self.stats
+                .update(storage, referrer.clone(), |stat| -> StdResult<_> {
+                    let mut stat = stat.unwrap_or_else(|| ReferrerStat::empty(referrer.clone()));
+                    stat.volume = stat.volume.checked_add(amount)?;
+                    if i == 0 && newly_active {
+                        stat.active_referees += 1;
+                    }
+                    Ok(stat)
+                })?;
+        }
+
+        Ok(chains)
+    }
+
+    pub fn stat_of(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<ReferrerStat> {
+        Ok(self
+            .stats
+            .may_load(storage, addr.clone())?
+            .unwrap_or_else(|| ReferrerStat::empty(addr.clone())))
+    }
+
+    /// Get the highest tier reached by a specific address, if any.
+    pub fn tier_of(&self, storage: &dyn Storage, addr: &Addr) -> StdResult<Option<Tier>> {
+        let stat = self.stat_of(storage, addr)?;
+
+        Ok(self.tiers(storage)?.into_iter().rev().find(|tier| {
+            stat.active_referees >= tier.min_active_referees && stat.volume >= tier.min_volume
+        }))
+    }
+
+    /// Get referrers ranked by accumulated volume, highest first.
+    pub fn leaderboard(
+        &self,
+        storage: &dyn Storage,
+        start_after: Option<Addr>,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<ReferrerStat>> {
+        let max = match start_after {
+            Some(addr) => {
+                let stat = self.stat_of(storage, &addr)?;
+                Some(Bound::Exclusive(
+                    (U128Key::new(stat.volume.u128()), addr.as_bytes().to_vec()).joined_key(),
+                ))
+            }
+            None => None,
+        };
+
+        self.stats
+            .index
+            .volume
+            .sub_prefix(())
+            .range(storage, None, max, Order::Descending)
+            .map(|e| e.map(|(_, stat)| stat))
+            .take(limit.unwrap_or(DEFAULT_LEADERBOARD_LIMIT) as usize)
+            .collect()
+    }
+}
@@ -1,23 +1,20 @@
 use cosmwasm_std::{StdResult, Storage};
-use cw_storage_plus::{Index, Map, Prefix, Prefixer, PrimaryKey};
+use cw_storage_plus::{Index, PrimaryKey};
 use serde::{de::DeserializeOwned, Serialize};
-use std::borrow::Cow;
 
-use super::{helpers::deserialize_multi_kv, DeserializeFn};
+use super::helpers::{deserialize_multi_kv, multi_entry_pk, DeserializeFn};
+use super::index::{IndexBuilder, IndexCow};
 
 #[derive(Clone)]
 pub struct ConditionalMultiIndex<'a, K, T> {
-    pub(crate) idx_namespace: Cow<'a, str>,
-    pub(crate) pk_namespace: Cow<'a, str>,
+    base: IndexBuilder<'a, T>,
     idx_fn: fn(&T, Vec<u8>) -> K,
-    cond_fn: fn(&T) -> bool,
-    dese_fn: Option<DeserializeFn<T>>,
 }
 
 impl<'a, K, T> ConditionalMultiIndex<'a, K, T> {
     /// Only if result of `cond_fn` is `true`, data will be added to this `ConditionalMultiIndex`.
     ///
-    /// Result of `cond_fn` **must be constant**, otherwise might raise unexpected behavior.
+    /// Data that stops matching on update is dropped from the index, and matching again adds it back.
     pub const fn new_ref(
         idx_fn: fn(&T, Vec<u8>) -> K,
         cond_fn: fn(&T) -> bool,
@@ -25,18 +22,15 @@ impl<'a, K, T> ConditionalMultiIndex<'a, K, T> {
         pk_namespace: &'a str,
         idx_namespace: &'a str,
     ) -> Self {
-        Self {
-            idx_fn,
-            cond_fn,
-            dese_fn,
-            idx_namespace: Cow::Borrowed(idx_namespace),
-            pk_namespace: Cow::Borrowed(pk_namespace),
-        }
+        let mut base = IndexBuilder::new_ref(pk_namespace, idx_namespace);
+        base.cond_fn = Some(cond_fn);
+        base.dese_fn = dese_fn;
+        Self { base, idx_fn }
     }
 
     /// Only if result of `cond_fn` is `true`, data will be added to this `ConditionalMultiIndex`.
     ///
-    /// Result of `cond_fn` **must be constant**, otherwise might raise unexpected behavior.
+    /// Data that stops matching on update is dropped from the index, and matching again adds it back.
     pub const fn new_owned(
         idx_fn: fn(&T, Vec<u8>) -> K,
         cond_fn: fn(&T) -> bool,
@@ -44,13 +38,10 @@ impl<'a, K, T> ConditionalMultiIndex<'a, K, T> {
         pk_namespace: String,
         idx_namespace: String,
     ) -> Self {
-        Self {
-            idx_fn,
-            cond_fn,
-            dese_fn,
-            idx_namespace: Cow::Owned(idx_namespace),
-            pk_namespace: Cow::Owned(pk_namespace),
-        }
+        let mut base = IndexBuilder::new_owned(pk_namespace, idx_namespace);
+        base.cond_fn = Some(cond_fn);
+        base.dese_fn = dese_fn;
+        Self { base, idx_fn }
     }
 }
 
@@ -60,69 +51,54 @@ where
     K: for<'key> PrimaryKey<'key>,
 {
     fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &T) -> StdResult<()> {
-        if (self.cond_fn)(data) {
-            let idx = (self.idx_fn)(data, pk.to_vec());
-            self.idx_map().save(store, idx, &(pk.len() as u32))?;
-        }
-
-        Ok(())
+        let idx = (self.idx_fn)(data, pk.to_vec()).joined_key();
+        self.base.save_multi(store, &idx, pk, data)
     }
 
     fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &T) -> StdResult<()> {
-        if (self.cond_fn)(old_data) {
-            let idx = (self.idx_fn)(old_data, pk.to_vec());
-            self.idx_map().remove(store, idx);
-        };
-
+        let idx = (self.idx_fn)(old_data, pk.to_vec()).joined_key();
+        self.base.remove_multi(store, &idx);
         Ok(())
     }
 }
 
-impl<'a, K, T> ConditionalMultiIndex<'a, K, T>
+impl<'a, K, T> IndexCow<T> for ConditionalMultiIndex<'a, K, T>
 where
     T: Serialize + DeserializeOwned + Clone,
     K: for<'key> PrimaryKey<'key>,
 {
-    fn idx_map(&self) -> Map<'_, K, u32> {
-        Map::new(&self.idx_namespace)
+    type Key = K;
+
+    fn pk_namespace(&self) -> &str {
+        &self.base.pk_namespace
     }
 
-    pub fn prefix(&self, p: <K as PrimaryKey<'_>>::Prefix) -> Prefix<T> {
-        Prefix::with_deserialization_function(
-            self.idx_namespace.as_bytes(),
-            &p.prefix(),
-            self.pk_namespace.as_bytes(),
-            match self.dese_fn {
-                Some(f) => f,
-                None => deserialize_multi_kv,
-            },
-        )
+    fn idx_namespace(&self) -> &str {
+        &self.base.idx_namespace
     }
 
-    pub fn sub_prefix(&self, p: <K as PrimaryKey<'_>>::SubPrefix) -> Prefix<T> {
-        Prefix::with_deserialization_function(
-            self.idx_namespace.as_bytes(),
-            &p.prefix(),
-            self.pk_namespace.as_bytes(),
-            match self.dese_fn {
-                Some(f) => f,
-                None => deserialize_multi_kv,
-            },
-        )
+    fn dese_fn(&self) -> DeserializeFn<T> {
+        self.base.dese_fn.unwrap_or(deserialize_multi_kv)
     }
 
-    pub fn index_key(&self, k: K) -> Vec<u8> {
-        k.joined_key()
+    fn entry_key(&self, pk: &[u8], data: &T) -> Option<Vec<u8>> {
+        self.base
+            .cond(data)
+            .then(|| (self.idx_fn)(data, pk.to_vec()).joined_key())
+    }
+
+    fn entry_pk(&self, key: &[u8], value: &[u8]) -> StdResult<Vec<u8>> {
+        Ok(multi_entry_pk(key, value)?.to_vec())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use cosmwasm_std::{testing::MockStorage, Uint128};
+    use cosmwasm_std::{testing::MockStorage, StdResult, Uint128};
     use cw_storage_plus::{Index, IndexList, IndexedMap, MultiIndex, PrimaryKey, U128Key, U64Key};
     use serde::{Deserialize, Serialize};
 
-    use crate::cow::deserialize_multi_kv_custom_pk;
+    use crate::cow::{deserialize_multi_kv_custom_pk, IndexCow, IndexedMapCow};
 
     use super::ConditionalMultiIndex;
 
@@ -145,50 +121,55 @@ mod test {
         }
     }
 
-    fn idm<'a>() -> IndexedMap<'a, U64Key, Test, TestIndexes<'a>> {
-        IndexedMap::new(
-            "test",
-            TestIndexes {
-                val: ConditionalMultiIndex::new_ref(
-                    |t, k| (t.val.u128().into(), k),
-                    // only add to val if t.val > 100
-                    |t| t.val.u128() > 100,
-                    None,
-                    "test",
-                    "test__val",
-                ),
-                val_inv: ConditionalMultiIndex::new_ref(
-                    |t, _| {
-                        (
-                            t.val.u128().into(),
-                            U64Key::new(u64::max_value() - t.id).joined_key(),
+    fn indexes<'a>() -> TestIndexes<'a> {
+        TestIndexes {
+            val: ConditionalMultiIndex::new_ref(
+                |t, k| (t.val.u128().into(), k),
+                // only add to val if t.val > 100
+                |t| t.val.u128() > 100,
+                None,
+                "test",
+                "test__val",
+            ),
+            val_inv: ConditionalMultiIndex::new_ref(
+                |t, _| {
+                    (
+                        t.val.u128().into(),
+                        U64Key::new(u64::max_value() - t.id).joined_key(),
+                    )
+                },
+                // only add to val if t.val > 100
+                |t| t.val.u128() > 100,
+                Some(|s, pk, kv| {
+                    deserialize_multi_kv_custom_pk(s, pk, kv, |old_kv| {
+                        U64Key::new(
+                            u64::max_value()
+                                - u64::from_be_bytes(old_kv.as_slice().try_into().unwrap()),
                         )
-                    },
-                    // only add to val if t.val > 100
-                    |t| t.val.u128() > 100,
-                    Some(|s, pk, kv| {
-                        deserialize_multi_kv_custom_pk(s, pk, kv, |old_kv| {
-                            U64Key::new(
-                                u64::max_value()
-                                    - u64::from_be_bytes(old_kv.as_slice().try_into().unwrap()),
-                            )
-                            .joined_key()
-                        })
-                    }),
-                    "test",
-                    "test__inv",
-                ),
-                val_n: MultiIndex::new(|t, k| (t.val.u128().into(), k), "test", "test__normal"),
-            },
-        )
+                        .joined_key()
+                    })
+                }),
+                "test",
+                "test__inv",
+            ),
+            val_n: MultiIndex::new(|t, k| (t.val.u128().into(), k), "test", "test__normal"),
+        }
+    }
+
+    fn idm<'a>() -> IndexedMap<'a, U64Key, Test, TestIndexes<'a>> {
+        IndexedMap::new("test", indexes())
+    }
+
+    fn cow<'a>() -> IndexedMapCow<'a, U64Key, Test, TestIndexes<'a>> {
+        IndexedMapCow::new_ref("test", indexes())
     }
 
     #[test]
     fn correct_namespace() {
         let idm = idm();
 
-        assert_eq!(idm.idx.val.pk_namespace, "test");
-        assert_eq!(idm.idx.val.idx_namespace, "test__val");
+        assert_eq!(idm.idx.val.pk_namespace(), "test");
+        assert_eq!(idm.idx.val.idx_namespace(), "test__val");
     }
 
     #[test]
@@ -304,4 +285,151 @@ mod test {
 
         assert_eq!(v_n, vec![(2, 101), (0, 101), (1, 100),]);
     }
+
+    fn val_ids(storage: &MockStorage) -> Vec<u64> {
+        cow()
+            .index
+            .val
+            .sub_prefix(())
+            .range(storage, None, None, cosmwasm_std::Order::Ascending)
+            .map(|e| e.unwrap().1.id)
+            .collect()
+    }
+
+    fn test(id: u64, val: u64) -> Test {
+        Test {
+            id,
+            val: Uint128::from(val),
+        }
+    }
+
+    #[test]
+    fn condition_becomes_true() {
+        let mut storage = MockStorage::new();
+        cow().save(&mut storage, 0.into(), &test(0, 50)).unwrap();
+        assert_eq!(val_ids(&storage), Vec::<u64>::new());
+
+        cow()
+            .update(&mut storage, 0.into(), |t| -> StdResult<_> {
+                let mut t = t.unwrap();
+                t.val = Uint128::from(150u64);
+                Ok(t)
+            })
+            .unwrap();
+        assert_eq!(val_ids(&storage), vec![0]);
+    }
+
+    #[test]
+    fn condition_becomes_false() {
+        let mut storage = MockStorage::new();
+        cow().save(&mut storage, 0.into(), &test(0, 150)).unwrap();
+        cow().save(&mut storage, 1.into(), &test(1, 150)).unwrap();
+        assert_eq!(val_ids(&storage), vec![0, 1]);
+
+        cow().save(&mut storage, 0.into(), &test(0, 50)).unwrap();
+        assert_eq!(val_ids(&storage), vec![1]);
+
+        cow()
+            .update(&mut storage, 1.into(), |t| -> StdResult<_> {
+                let mut t = t.unwrap();
+                t.val = Uint128::from(100u64);
+                Ok(t)
+            })
+            .unwrap();
+        assert_eq!(val_ids(&storage), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn index_key_changes() {
+        let mut storage = MockStorage::new();
+        cow().save(&mut storage, 0.into(), &test(0, 150)).unwrap();
+        cow().save(&mut storage, 0.into(), &test(0, 250)).unwrap();
+
+        let v = cow()
+            .index
+            .val
+            .prefix(250u128.into())
+            .range(&storage, None, None, cosmwasm_std::Order::Ascending)
+            .map(|e| e.unwrap().1.id)
+            .collect::<Vec<_>>();
+        assert_eq!(v, vec![0]);
+        assert_eq!(val_ids(&storage), vec![0]);
+
+        cow().remove(&mut storage, 0.into()).unwrap();
+        assert_eq!(val_ids(&storage), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn rebuild_repairs_diverged_index() {
+        let mut storage = MockStorage::new();
+        for (id, val) in [(0, 50), (1, 150), (2, 250)] {
+            cow().save(&mut storage, id.into(), &test(id, val)).unwrap();
+        }
+
+        // index written by an older condition: a stale entry and a missing one
+        let old: ConditionalMultiIndex<(U128Key, Vec<u8>), Test> = ConditionalMultiIndex::new_ref(
+            |t, k| (t.val.u128().into(), k),
+            |t| t.val.u128() < 200,
+            None,
+            "test",
+            "test__val",
+        );
+        let pk = |id: u64| U64Key::new(id).joined_key();
+        old.save(&mut storage, &pk(0), &test(0, 50)).unwrap();
+        old.remove(&mut storage, &pk(2), &test(2, 250)).unwrap();
+        assert_eq!(val_ids(&storage), vec![0, 1]);
+
+        let map = cow();
+        let mut cursor = None;
+        loop {
+            cursor = map
+                .rebuild_index(&mut storage, &map.index.val, cursor, 2)
+                .unwrap();
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(val_ids(&storage), vec![1, 2]);
+    }
+
+    #[test]
+    fn rebuild_drops_entries_no_longer_matching() {
+        let mut storage = MockStorage::new();
+        for (id, val) in [(0, 50), (1, 150), (2, 250)] {
+            cow().save(&mut storage, id.into(), &test(id, val)).unwrap();
+        }
+
+        // index written while every record matched, under a key no longer produced
+        let old: ConditionalMultiIndex<(U128Key, Vec<u8>), Test> = ConditionalMultiIndex::new_ref(
+            |t, k| ((t.val.u128() + 1).into(), k),
+            |_| true,
+            None,
+            "test",
+            "test__val",
+        );
+        let pk = |id: u64| U64Key::new(id).joined_key();
+        old.save(&mut storage, &pk(0), &test(0, 50)).unwrap();
+        old.save(&mut storage, &pk(2), &test(2, 250)).unwrap();
+
+        let map = cow();
+        assert!(!map
+            .verify_index(&storage, &map.index.val)
+            .unwrap()
+            .is_consistent());
+
+        let mut cursor = None;
+        loop {
+            cursor = map
+                .rebuild_index(&mut storage, &map.index.val, cursor, 2)
+                .unwrap();
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(val_ids(&storage), vec![1, 2]);
+        assert!(map
+            .verify_index(&storage, &map.index.val)
+            .unwrap()
+            .is_consistent());
+    }
 }
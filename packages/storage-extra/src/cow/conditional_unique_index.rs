@@ -0,0 +1,237 @@
+use cosmwasm_std::{StdResult, Storage};
+use cw_storage_plus::{Index, PrimaryKey};
+use serde::{de::DeserializeOwned, Serialize};
+use std::ops::Deref;
+
+use super::helpers::DeserializeFn;
+use super::index::{IndexBuilder, IndexCow};
+use super::indexed_map::UniqueIndexCow;
+
+/// `UniqueIndex` enforced only on data for which `cond_fn` returns `true`, other data may share
+/// the same index value.
+///
+/// Shorthand for `IndexBuilder::with_cond(..).unique(..)`, lookups like `load_by` are the ones of
+/// `UniqueIndexCow`.
+#[derive(Clone)]
+pub struct ConditionalUniqueIndex<'a, K, T> {
+    index: UniqueIndexCow<'a, K, T>,
+}
+
+impl<'a, K, T> ConditionalUniqueIndex<'a, K, T> {
+    /// Only if result of `cond_fn` is `true`, data will be added to this `ConditionalUniqueIndex`.
+    ///
+    /// Data leaving the condition on update releases its index value.
+    pub const fn new_ref(
+        idx_fn: fn(&T) -> K,
+        cond_fn: fn(&T) -> bool,
+        idx_namespace: &'a str,
+    ) -> Self {
+        Self {
+            index: IndexBuilder::new_ref("", idx_namespace)
+                .with_cond(cond_fn)
+                .unique(idx_fn),
+        }
+    }
+
+    /// Only if result of `cond_fn` is `true`, data will be added to this `ConditionalUniqueIndex`.
+    ///
+    /// Data leaving the condition on update releases its index value.
+    pub const fn new_owned(
+        idx_fn: fn(&T) -> K,
+        cond_fn: fn(&T) -> bool,
+        idx_namespace: String,
+    ) -> Self {
+        Self {
+            index: IndexBuilder::new_owned(String::new(), idx_namespace)
+                .with_cond(cond_fn)
+                .unique(idx_fn),
+        }
+    }
+}
+
+impl<'a, K, T> Deref for ConditionalUniqueIndex<'a, K, T> {
+    type Target = UniqueIndexCow<'a, K, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.index
+    }
+}
+
+impl<'a, K, T> Index<T> for ConditionalUniqueIndex<'a, K, T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+    K: for<'key> PrimaryKey<'key>,
+{
+    fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &T) -> StdResult<()> {
+        self.index.save(store, pk, data)
+    }
+
+    fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &T) -> StdResult<()> {
+        // removal only drops an entry owned by `pk`, whatever `cond_fn` says now
+        self.index.remove(store, pk, old_data)
+    }
+}
+
+impl<'a, K, T> IndexCow<T> for ConditionalUniqueIndex<'a, K, T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+    K: for<'key> PrimaryKey<'key>,
+{
+    type Key = K;
+
+    fn pk_namespace(&self) -> &str {
+        self.index.pk_namespace()
+    }
+
+    fn idx_namespace(&self) -> &str {
+        self.index.idx_namespace()
+    }
+
+    fn dese_fn(&self) -> DeserializeFn<T> {
+        self.index.dese_fn()
+    }
+
+    fn entry_key(&self, pk: &[u8], data: &T) -> Option<Vec<u8>> {
+        self.index.entry_key(pk, data)
+    }
+
+    fn entry_pk(&self, key: &[u8], value: &[u8]) -> StdResult<Vec<u8>> {
+        self.index.entry_pk(key, value)
+    }
+
+    fn entry_is_stale(&self, value: &[u8], data: &T) -> StdResult<bool> {
+        self.index.entry_is_stale(value, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cosmwasm_std::{testing::MockStorage, Addr, Order};
+    use cw_storage_plus::{Index, IndexList, IndexedMap, U64Key};
+    use serde::{Deserialize, Serialize};
+
+    use crate::cow::{IndexCow, UniqueViolation};
+
+    use super::ConditionalUniqueIndex;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct UserOrder {
+        id: u64,
+        user: Addr,
+        active: bool,
+    }
+
+    struct OrderIndexes<'a> {
+        active_user: ConditionalUniqueIndex<'a, Addr, UserOrder>,
+    }
+
+    impl IndexList<UserOrder> for OrderIndexes<'_> {
+        fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<UserOrder>> + '_> {
+            let v: Vec<&dyn Index<UserOrder>> = vec![&self.active_user];
+            Box::new(v.into_iter())
+        }
+    }
+
+    fn idm<'a>() -> IndexedMap<'a, U64Key, UserOrder, OrderIndexes<'a>> {
+        IndexedMap::new(
+            "order",
+            OrderIndexes {
+                active_user: ConditionalUniqueIndex::new_ref(
+                    |o| o.user.clone(),
+                    // only one active order per user
+                    |o| o.active,
+                    "order__active_user",
+                ),
+            },
+        )
+    }
+
+    fn order(id: u64, user: &str, active: bool) -> UserOrder {
+        UserOrder {
+            id,
+            user: Addr::unchecked(user),
+            active,
+        }
+    }
+
+    #[test]
+    fn only_matching_data_is_unique() {
+        let mut storage = MockStorage::new();
+
+        // inactive orders may share the same user
+        idm()
+            .save(&mut storage, 0.into(), &order(0, "a", false))
+            .unwrap();
+        idm()
+            .save(&mut storage, 1.into(), &order(1, "a", false))
+            .unwrap();
+        idm()
+            .save(&mut storage, 2.into(), &order(2, "a", true))
+            .unwrap();
+
+        let err = idm()
+            .save(&mut storage, 3.into(), &order(3, "a", true))
+            .unwrap_err();
+        assert!(UniqueViolation::from_std_error(&err).is_some());
+
+        // activating another order of the same user is rejected too
+        idm()
+            .save(&mut storage, 1.into(), &order(1, "a", true))
+            .unwrap_err();
+
+        assert_eq!(
+            idm()
+                .idx
+                .active_user
+                .load_by(&storage, Addr::unchecked("a"))
+                .unwrap()
+                .1,
+            order(2, "a", true)
+        );
+
+        // removing an inactive duplicate keeps the active entry
+        idm().remove(&mut storage, 0.into()).unwrap();
+        assert_eq!(
+            idm()
+                .idx
+                .active_user
+                .sub_prefix(())
+                .range(&storage, None, None, Order::Ascending)
+                .map(|e| e.unwrap().1.id)
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn deactivation_releases_key() {
+        let mut storage = MockStorage::new();
+        const ORDERS: ConditionalUniqueIndex<Addr, UserOrder> =
+            ConditionalUniqueIndex::new_ref(|o| o.user.clone(), |o| o.active, "order__active_user");
+        assert_eq!(ORDERS.idx_namespace(), "order__active_user");
+
+        idm()
+            .save(&mut storage, 0.into(), &order(0, "a", true))
+            .unwrap();
+        idm()
+            .save(&mut storage, 0.into(), &order(0, "a", false))
+            .unwrap();
+
+        assert_eq!(
+            idm()
+                .idx
+                .active_user
+                .may_load_by(&storage, Addr::unchecked("a"))
+                .unwrap(),
+            None
+        );
+
+        idm()
+            .save(&mut storage, 1.into(), &order(1, "a", true))
+            .unwrap();
+        assert_eq!(
+            ORDERS.load_by(&storage, Addr::unchecked("a")).unwrap().1,
+            order(1, "a", true)
+        );
+    }
+}
@@ -1,16 +1,19 @@
 use cosmwasm_std::{StdResult, Storage};
-use cw_storage_plus::{Index, Map, Prefix, Prefixer, PrimaryKey};
+use cw_storage_plus::{Index, PrimaryKey};
 use serde::{de::DeserializeOwned, Serialize};
-use std::borrow::Cow;
 
-use super::helpers::{deserialize_multi_kv, DeserializeFn};
+use super::helpers::{deserialize_multi_kv, multi_entry_pk, DeserializeFn};
+use super::index::{IndexBuilder, IndexCow};
+use super::transform::{deserialize_multi_kv_transformed, KeyTransform};
+
+type PkFn = fn(Vec<u8>) -> Vec<u8>;
 
 #[derive(Clone)]
 pub struct CustomDeseMultiIndex<'a, K, T> {
-    pub(crate) idx_namespace: Cow<'a, str>,
-    pub(crate) pk_namespace: Cow<'a, str>,
+    base: IndexBuilder<'a, T>,
     idx_fn: fn(&T, Vec<u8>) -> K,
-    dese_fn: Option<DeserializeFn<T>>,
+    // (forward, inverse) applied to pk when set by a transformed constructor
+    pk_transform: Option<(PkFn, PkFn)>,
 }
 
 impl<'a, K, T> CustomDeseMultiIndex<'a, K, T> {
@@ -20,11 +23,12 @@ impl<'a, K, T> CustomDeseMultiIndex<'a, K, T> {
         pk_namespace: &'a str,
         idx_namespace: &'a str,
     ) -> Self {
+        let mut base = IndexBuilder::new_ref(pk_namespace, idx_namespace);
+        base.dese_fn = dese_fn;
         Self {
+            base,
             idx_fn,
-            dese_fn,
-            idx_namespace: Cow::Borrowed(idx_namespace),
-            pk_namespace: Cow::Borrowed(pk_namespace),
+            pk_transform: None,
         }
     }
 
@@ -33,68 +37,108 @@ impl<'a, K, T> CustomDeseMultiIndex<'a, K, T> {
         dese_fn: Option<DeserializeFn<T>>,
         pk_namespace: String,
         idx_namespace: String,
+    ) -> Self {
+        let mut base = IndexBuilder::new_owned(pk_namespace, idx_namespace);
+        base.dese_fn = dese_fn;
+        Self {
+            base,
+            idx_fn,
+            pk_transform: None,
+        }
+    }
+}
+
+impl<'a, K, T: DeserializeOwned> CustomDeseMultiIndex<'a, K, T> {
+    /// `idx_fn` receives pk transformed by `X::forward`, which is undone by `X::inverse` when
+    /// iterating, e.g. `Invert` for descending ids.
+    pub const fn new_ref_transformed<X: KeyTransform>(
+        idx_fn: fn(&T, Vec<u8>) -> K,
+        pk_namespace: &'a str,
+        idx_namespace: &'a str,
+    ) -> Self {
+        Self {
+            base: IndexBuilder::new_ref(pk_namespace, idx_namespace)
+                .with_dese(deserialize_multi_kv_transformed::<T, X>),
+            idx_fn,
+            pk_transform: Some((X::forward, X::inverse)),
+        }
+    }
+
+    /// `idx_fn` receives pk transformed by `X::forward`, which is undone by `X::inverse` when
+    /// iterating, e.g. `Invert` for descending ids.
+    pub const fn new_owned_transformed<X: KeyTransform>(
+        idx_fn: fn(&T, Vec<u8>) -> K,
+        pk_namespace: String,
+        idx_namespace: String,
     ) -> Self {
         Self {
+            base: IndexBuilder::new_owned(pk_namespace, idx_namespace)
+                .with_dese(deserialize_multi_kv_transformed::<T, X>),
             idx_fn,
-            dese_fn,
-            idx_namespace: Cow::Owned(idx_namespace),
-            pk_namespace: Cow::Owned(pk_namespace),
+            pk_transform: Some((X::forward, X::inverse)),
         }
     }
 }
 
+impl<K, T> CustomDeseMultiIndex<'_, K, T>
+where
+    K: for<'key> PrimaryKey<'key>,
+{
+    fn idx(&self, data: &T, pk: &[u8]) -> Vec<u8> {
+        let pk = match self.pk_transform {
+            Some((forward, _)) => forward(pk.to_vec()),
+            None => pk.to_vec(),
+        };
+        (self.idx_fn)(data, pk).joined_key()
+    }
+}
+
 impl<'a, K, T> Index<T> for CustomDeseMultiIndex<'a, K, T>
 where
     T: Serialize + DeserializeOwned + Clone,
     K: for<'key> PrimaryKey<'key>,
 {
     fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &T) -> StdResult<()> {
-        let idx = (self.idx_fn)(data, pk.to_vec());
-        self.idx_map().save(store, idx, &(pk.len() as u32))
+        let idx = self.idx(data, pk);
+        self.base.save_multi(store, &idx, pk, data)
     }
 
     fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &T) -> StdResult<()> {
-        let idx = (self.idx_fn)(old_data, pk.to_vec());
-        self.idx_map().remove(store, idx);
+        let idx = self.idx(old_data, pk);
+        self.base.remove_multi(store, &idx);
         Ok(())
     }
 }
 
-impl<'a, K, T> CustomDeseMultiIndex<'a, K, T>
+impl<'a, K, T> IndexCow<T> for CustomDeseMultiIndex<'a, K, T>
 where
     T: Serialize + DeserializeOwned + Clone,
     K: for<'key> PrimaryKey<'key>,
 {
-    fn idx_map(&self) -> Map<'_, K, u32> {
-        Map::new(&self.idx_namespace)
+    type Key = K;
+
+    fn pk_namespace(&self) -> &str {
+        &self.base.pk_namespace
     }
 
-    pub fn prefix(&self, p: <K as PrimaryKey<'_>>::Prefix) -> Prefix<T> {
-        Prefix::with_deserialization_function(
-            self.idx_namespace.as_bytes(),
-            &p.prefix(),
-            self.pk_namespace.as_bytes(),
-            match self.dese_fn {
-                Some(f) => f,
-                None => deserialize_multi_kv,
-            },
-        )
+    fn idx_namespace(&self) -> &str {
+        &self.base.idx_namespace
     }
 
-    pub fn sub_prefix(&self, p: <K as PrimaryKey<'_>>::SubPrefix) -> Prefix<T> {
-        Prefix::with_deserialization_function(
-            self.idx_namespace.as_bytes(),
-            &p.prefix(),
-            self.pk_namespace.as_bytes(),
-            match self.dese_fn {
-                Some(f) => f,
-                None => deserialize_multi_kv,
-            },
-        )
+    fn dese_fn(&self) -> DeserializeFn<T> {
+        self.base.dese_fn.unwrap_or(deserialize_multi_kv)
     }
 
-    pub fn index_key(&self, k: K) -> Vec<u8> {
-        k.joined_key()
+    fn entry_key(&self, pk: &[u8], data: &T) -> Option<Vec<u8>> {
+        self.base.cond(data).then(|| self.idx(data, pk))
+    }
+
+    fn entry_pk(&self, key: &[u8], value: &[u8]) -> StdResult<Vec<u8>> {
+        let pk = multi_entry_pk(key, value)?.to_vec();
+        Ok(match self.pk_transform {
+            Some((_, inverse)) => inverse(pk),
+            None => pk,
+        })
     }
 }
 
@@ -104,7 +148,7 @@ mod test {
     use cw_storage_plus::{Index, IndexList, IndexedMap, MultiIndex, PrimaryKey, U128Key, U64Key};
     use serde::{Deserialize, Serialize};
 
-    use crate::cow::deserialize_multi_kv_custom_pk;
+    use crate::cow::{deserialize_multi_kv_custom_pk, IndexCow, IndexInspect, Invert};
 
     use super::CustomDeseMultiIndex;
 
@@ -117,11 +161,12 @@ mod test {
     struct TestIndexes<'a> {
         val: CustomDeseMultiIndex<'a, (U128Key, Vec<u8>), Test>,
         val_n: MultiIndex<'a, (U128Key, Vec<u8>), Test>,
+        val_t: CustomDeseMultiIndex<'a, (U128Key, Vec<u8>), Test>,
     }
 
     impl IndexList<Test> for TestIndexes<'_> {
         fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Test>> + '_> {
-            let v: Vec<&dyn Index<Test>> = vec![&self.val, &self.val_n];
+            let v: Vec<&dyn Index<Test>> = vec![&self.val, &self.val_n, &self.val_t];
             Box::new(v.into_iter())
         }
     }
@@ -150,6 +195,11 @@ mod test {
                     "test__val",
                 ),
                 val_n: MultiIndex::new(|t, k| (t.val.u128().into(), k), "test", "test__val_n"),
+                val_t: CustomDeseMultiIndex::new_ref_transformed::<Invert>(
+                    |t, k| (t.val.u128().into(), k),
+                    "test",
+                    "test__val_t",
+                ),
             },
         )
     }
@@ -158,8 +208,8 @@ mod test {
     fn correct_namespace() {
         let idm = idm();
 
-        assert_eq!(idm.idx.val.pk_namespace, "test");
-        assert_eq!(idm.idx.val.idx_namespace, "test__val");
+        assert_eq!(idm.idx.val.pk_namespace(), "test");
+        assert_eq!(idm.idx.val.idx_namespace(), "test__val");
     }
 
     #[test]
@@ -258,4 +308,60 @@ mod test {
         // val: Descending, id: Descending
         assert_eq!(vn, vec![2, 3, 1, 0]);
     }
+
+    #[test]
+    fn transformed_pairs_forward_and_inverse() {
+        let mut storage = MockStorage::new();
+        for (id, val) in [(0u64, 100u64), (1, 100), (2, 200), (3, 100)] {
+            idm()
+                .save(
+                    &mut storage,
+                    id.into(),
+                    &Test {
+                        id,
+                        val: Uint128::from(val),
+                    },
+                )
+                .unwrap();
+        }
+
+        let idm = idm();
+        let entries = idm
+            .idx
+            .val_t
+            .sub_prefix_de::<U64Key>(())
+            .range(&storage, None, None, Order::Descending)
+            .map(|e| e.unwrap())
+            .collect::<Vec<_>>();
+
+        // same order as the hand written deserializer, with pk recovered
+        assert_eq!(
+            entries
+                .iter()
+                .map(|(pk, t)| (*pk, t.id))
+                .collect::<Vec<_>>(),
+            vec![(2, 2), (0, 0), (1, 1), (3, 3)]
+        );
+
+        // consistency checks see the original pk too
+        let pk = U64Key::new(1).joined_key();
+        let key = idm
+            .idx
+            .val_t
+            .key_of(
+                &pk,
+                &Test {
+                    id: 1,
+                    val: Uint128::from(100u64),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            idm.idx
+                .val_t
+                .pk_of(&key, &cosmwasm_std::to_vec(&(pk.len() as u32)).unwrap())
+                .unwrap(),
+            pk
+        );
+    }
 }
@@ -0,0 +1,338 @@
+use cosmwasm_std::{from_slice, to_vec, Order, StdError, StdResult, Storage};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{borrow::Cow, marker::PhantomData};
+
+use super::helpers::namespaces_with_key;
+
+const HEAD_KEY: &[u8] = b"h";
+const TAIL_KEY: &[u8] = b"t";
+
+type DequeIter<'c, T> = Box<dyn Iterator<Item = StdResult<(u32, T)>> + 'c>;
+
+/// Double ended queue, values are kept under the namespace by a wrapping `u32` position between
+/// head (inclusive) and tail (exclusive), both stored under the namespace too.
+#[derive(Debug, Clone)]
+pub struct DequeCow<'a, T> {
+    pub(crate) namespace: Cow<'a, str>,
+    data_type: PhantomData<T>,
+}
+
+impl<'a, 'k, T> DequeCow<'a, T>
+where
+    'k: 'a,
+{
+    pub const fn new_owned(namespace: String) -> Self {
+        Self {
+            namespace: Cow::Owned(namespace),
+            data_type: PhantomData,
+        }
+    }
+
+    pub const fn new_ref(namespace: &'k str) -> Self {
+        Self {
+            namespace: Cow::Borrowed(namespace),
+            data_type: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> DequeCow<'a, T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn raw_key(&self, key: &[u8]) -> Vec<u8> {
+        namespaces_with_key(&[self.namespace.as_bytes()], key)
+    }
+
+    fn load_position(&self, store: &dyn Storage, key: &[u8]) -> StdResult<u32> {
+        store
+            .get(&self.raw_key(key))
+            .map(|v| {
+                v.as_slice()
+                    .try_into()
+                    .map(u32::from_be_bytes)
+                    .map_err(|_| StdError::generic_err("Corrupted deque position"))
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    fn save_position(&self, store: &mut dyn Storage, key: &[u8], position: u32) {
+        store.set(&self.raw_key(key), &position.to_be_bytes());
+    }
+
+    fn load_at(&self, store: &dyn Storage, position: u32) -> StdResult<Option<T>> {
+        store
+            .get(&self.raw_key(&position.to_be_bytes()))
+            .map(|v| from_slice(&v))
+            .transpose()
+    }
+
+    fn bounds(&self, store: &dyn Storage) -> StdResult<(u32, u32)> {
+        Ok((
+            self.load_position(store, HEAD_KEY)?,
+            self.load_position(store, TAIL_KEY)?,
+        ))
+    }
+
+    pub fn len(&self, store: &dyn Storage) -> StdResult<u32> {
+        let (head, tail) = self.bounds(store)?;
+        Ok(tail.wrapping_sub(head))
+    }
+
+    pub fn is_empty(&self, store: &dyn Storage) -> StdResult<bool> {
+        Ok(self.len(store)? == 0)
+    }
+
+    fn check_capacity(&self, store: &dyn Storage) -> StdResult<()> {
+        if self.len(store)? == u32::MAX {
+            return Err(StdError::generic_err("Deque is full"));
+        }
+        Ok(())
+    }
+
+    pub fn push_back(&self, store: &mut dyn Storage, data: &T) -> StdResult<()> {
+        self.check_capacity(store)?;
+        let tail = self.load_position(store, TAIL_KEY)?;
+        store.set(&self.raw_key(&tail.to_be_bytes()), &to_vec(data)?);
+        self.save_position(store, TAIL_KEY, tail.wrapping_add(1));
+        Ok(())
+    }
+
+    pub fn push_front(&self, store: &mut dyn Storage, data: &T) -> StdResult<()> {
+        self.check_capacity(store)?;
+        let head = self.load_position(store, HEAD_KEY)?.wrapping_sub(1);
+        store.set(&self.raw_key(&head.to_be_bytes()), &to_vec(data)?);
+        self.save_position(store, HEAD_KEY, head);
+        Ok(())
+    }
+
+    pub fn pop_front(&self, store: &mut dyn Storage) -> StdResult<Option<T>> {
+        let (head, tail) = self.bounds(store)?;
+        if head == tail {
+            return Ok(None);
+        }
+
+        let value = self.load_at(store, head)?;
+        store.remove(&self.raw_key(&head.to_be_bytes()));
+        self.save_position(store, HEAD_KEY, head.wrapping_add(1));
+        Ok(value)
+    }
+
+    pub fn pop_back(&self, store: &mut dyn Storage) -> StdResult<Option<T>> {
+        let (head, tail) = self.bounds(store)?;
+        if head == tail {
+            return Ok(None);
+        }
+
+        let tail = tail.wrapping_sub(1);
+        let value = self.load_at(store, tail)?;
+        store.remove(&self.raw_key(&tail.to_be_bytes()));
+        self.save_position(store, TAIL_KEY, tail);
+        Ok(value)
+    }
+
+    pub fn front(&self, store: &dyn Storage) -> StdResult<Option<T>> {
+        self.get(store, 0)
+    }
+
+    pub fn back(&self, store: &dyn Storage) -> StdResult<Option<T>> {
+        match self.len(store)? {
+            0 => Ok(None),
+            len => self.get(store, len - 1),
+        }
+    }
+
+    /// Value at `index` counted from the front, `None` if out of bounds.
+    pub fn get(&self, store: &dyn Storage, index: u32) -> StdResult<Option<T>> {
+        let (head, tail) = self.bounds(store)?;
+        if index >= tail.wrapping_sub(head) {
+            return Ok(None);
+        }
+        self.load_at(store, head.wrapping_add(index))
+    }
+
+    /// Iterate `(position, value)` starting after position `start_after`.
+    ///
+    /// Positions are the absolute ones values are stored at, they do not shift when values are
+    /// popped, so a `start_after` taken from a previous page stays valid. A `start_after` popped
+    /// since then is treated as lying before the front, or past the back, whichever is closer.
+    pub fn range<'c>(
+        &self,
+        store: &'c dyn Storage,
+        start_after: Option<u32>,
+        order: Order,
+    ) -> StdResult<DequeIter<'c, T>>
+    where
+        T: 'c,
+    {
+        let (head, tail) = self.bounds(store)?;
+        let len = tail.wrapping_sub(head);
+        // offset of `start_after` from head, -1 if before head and `len` if past tail
+        let after = start_after.map(|position| {
+            let offset = position.wrapping_sub(head);
+            if offset < len {
+                offset as i64
+            } else if head.wrapping_sub(position) <= position.wrapping_sub(tail) {
+                -1
+            } else {
+                len as i64
+            }
+        });
+        let len = len as i64;
+        let offsets: Box<dyn Iterator<Item = i64>> = match (order, after) {
+            (Order::Ascending, None) => Box::new(0..len),
+            (Order::Ascending, Some(i)) => Box::new((i + 1).min(len)..len),
+            (Order::Descending, None) => Box::new((0..len).rev()),
+            (Order::Descending, Some(i)) => Box::new((0..i.max(0)).rev()),
+        };
+
+        let namespace = self.namespace.to_string();
+        Ok(Box::new(offsets.map(move |i| {
+            let position = head.wrapping_add(i as u32);
+            let key = namespaces_with_key(&[namespace.as_bytes()], &position.to_be_bytes());
+            let value = store
+                .get(&key)
+                .ok_or_else(|| StdError::not_found("deque value"))?;
+            Ok((position, from_slice(&value)?))
+        })))
+    }
+
+    /// Page of `(position, value)`, see `range`.
+    pub fn page(
+        &self,
+        store: &dyn Storage,
+        start_after: Option<u32>,
+        limit: usize,
+        order: Order,
+    ) -> StdResult<Vec<(u32, T)>> {
+        self.range(store, start_after, order)?.take(limit).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+
+    #[test]
+    fn push_pop() {
+        let mut storage = MockStorage::new();
+        const QUEUE: DequeCow<u64> = DequeCow::new_ref("queue");
+
+        assert_eq!(QUEUE.pop_front(&mut storage).unwrap(), None);
+        assert_eq!(QUEUE.pop_back(&mut storage).unwrap(), None);
+        assert!(QUEUE.is_empty(&storage).unwrap());
+
+        QUEUE.push_back(&mut storage, &2).unwrap();
+        QUEUE.push_back(&mut storage, &3).unwrap();
+        // head wraps below zero
+        QUEUE.push_front(&mut storage, &1).unwrap();
+        QUEUE.push_front(&mut storage, &0).unwrap();
+
+        assert_eq!(QUEUE.len(&storage).unwrap(), 4);
+        assert_eq!(QUEUE.front(&storage).unwrap(), Some(0));
+        assert_eq!(QUEUE.back(&storage).unwrap(), Some(3));
+        assert_eq!(QUEUE.get(&storage, 2).unwrap(), Some(2));
+        assert_eq!(QUEUE.get(&storage, 4).unwrap(), None);
+
+        assert_eq!(QUEUE.pop_front(&mut storage).unwrap(), Some(0));
+        assert_eq!(QUEUE.pop_back(&mut storage).unwrap(), Some(3));
+        assert_eq!(QUEUE.pop_back(&mut storage).unwrap(), Some(2));
+        assert_eq!(QUEUE.pop_back(&mut storage).unwrap(), Some(1));
+        assert_eq!(QUEUE.pop_front(&mut storage).unwrap(), None);
+        assert!(QUEUE.is_empty(&storage).unwrap());
+        assert_eq!(QUEUE.front(&storage).unwrap(), None);
+        assert_eq!(QUEUE.back(&storage).unwrap(), None);
+    }
+
+    #[test]
+    fn paginate() {
+        let mut storage = MockStorage::new();
+        let queue: DequeCow<u64> = DequeCow::new_owned("pool_withdrawals".to_string());
+
+        for i in 0..5 {
+            queue.push_back(&mut storage, &(i * 10)).unwrap();
+        }
+        queue.pop_front(&mut storage).unwrap();
+
+        assert_eq!(
+            queue.page(&storage, None, 2, Order::Ascending).unwrap(),
+            vec![(1, 10), (2, 20)]
+        );
+        assert_eq!(
+            queue.page(&storage, Some(2), 10, Order::Ascending).unwrap(),
+            vec![(3, 30), (4, 40)]
+        );
+        assert_eq!(
+            queue.page(&storage, None, 2, Order::Descending).unwrap(),
+            vec![(4, 40), (3, 30)]
+        );
+        assert_eq!(
+            queue
+                .page(&storage, Some(3), 10, Order::Descending)
+                .unwrap(),
+            vec![(2, 20), (1, 10)]
+        );
+        assert_eq!(
+            queue
+                .page(&storage, Some(10), 10, Order::Ascending)
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn paginate_while_popping() {
+        let mut storage = MockStorage::new();
+        const QUEUE: DequeCow<u64> = DequeCow::new_ref("queue");
+
+        for i in 1..=4 {
+            QUEUE.push_back(&mut storage, &i).unwrap();
+        }
+        // head wraps below zero
+        QUEUE.push_front(&mut storage, &0).unwrap();
+
+        let page = QUEUE.page(&storage, None, 2, Order::Ascending).unwrap();
+        assert_eq!(page, vec![(u32::MAX, 0), (0, 1)]);
+
+        // popping does not shift the next page
+        QUEUE.pop_front(&mut storage).unwrap();
+        let cursor = page.last().map(|(position, _)| *position);
+        assert_eq!(
+            QUEUE.page(&storage, cursor, 2, Order::Ascending).unwrap(),
+            vec![(1, 2), (2, 3)]
+        );
+
+        // a popped cursor restarts from the front
+        QUEUE.pop_front(&mut storage).unwrap();
+        QUEUE.pop_front(&mut storage).unwrap();
+        assert_eq!(
+            QUEUE.page(&storage, cursor, 2, Order::Ascending).unwrap(),
+            vec![(2, 3), (3, 4)]
+        );
+
+        // and is past the back when popped from there
+        QUEUE.pop_back(&mut storage).unwrap();
+        assert_eq!(
+            QUEUE.page(&storage, Some(3), 2, Order::Ascending).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            QUEUE.page(&storage, Some(3), 2, Order::Descending).unwrap(),
+            vec![(2, 3)]
+        );
+    }
+
+    #[test]
+    fn full() {
+        let mut storage = MockStorage::new();
+        const QUEUE: DequeCow<u64> = DequeCow::new_ref("queue");
+
+        // fake a full queue
+        QUEUE.save_position(&mut storage, TAIL_KEY, u32::MAX);
+
+        QUEUE.push_back(&mut storage, &1).unwrap_err();
+        QUEUE.push_front(&mut storage, &1).unwrap_err();
+    }
+}
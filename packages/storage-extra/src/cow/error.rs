@@ -0,0 +1,88 @@
+use cosmwasm_std::{Binary, StdError};
+use std::{convert::TryFrom, fmt};
+
+const UNIQUE_VIOLATION_PREFIX: &str = "Violates unique constraint on index ";
+const UNIQUE_VIOLATION_KEY: &str = " with key ";
+
+/// Unique constraint violation of an unique index.
+///
+/// `Index` can only return `StdError`, so violation travels as `StdError::GenericErr` and can be
+/// recovered with `UniqueViolation::from_std_error` or `TryFrom<&StdError>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UniqueViolation {
+    /// Namespace of violated index
+    pub index: String,
+    /// Joined index key that is already taken
+    pub key: Binary,
+}
+
+impl UniqueViolation {
+    pub fn from_std_error(err: &StdError) -> Option<Self> {
+        Self::try_from(err).ok()
+    }
+}
+
+impl fmt::Display for UniqueViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}",
+            UNIQUE_VIOLATION_PREFIX,
+            self.index,
+            UNIQUE_VIOLATION_KEY,
+            self.key.to_base64()
+        )
+    }
+}
+
+impl From<UniqueViolation> for StdError {
+    fn from(violation: UniqueViolation) -> Self {
+        StdError::generic_err(violation.to_string())
+    }
+}
+
+impl TryFrom<&StdError> for UniqueViolation {
+    type Error = ();
+
+    fn try_from(err: &StdError) -> Result<Self, Self::Error> {
+        let msg = match err {
+            StdError::GenericErr { msg, .. } => msg,
+            _ => return Err(()),
+        };
+
+        let (index, key) = msg
+            .strip_prefix(UNIQUE_VIOLATION_PREFIX)
+            .and_then(|rest| rest.rsplit_once(UNIQUE_VIOLATION_KEY))
+            .ok_or(())?;
+
+        Ok(UniqueViolation {
+            index: index.to_string(),
+            key: Binary::from_base64(key).map_err(|_| ())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip_std_error() {
+        let violation = UniqueViolation {
+            index: "primary_address".to_string(),
+            key: Binary::from(b"addr".to_vec()),
+        };
+
+        let err: StdError = violation.clone().into();
+
+        assert_eq!(UniqueViolation::from_std_error(&err), Some(violation));
+        assert_eq!(
+            UniqueViolation::from_std_error(&StdError::generic_err("pk not found")),
+            None
+        );
+        assert_eq!(
+            UniqueViolation::from_std_error(&StdError::not_found("data")),
+            None
+        );
+    }
+}
@@ -1,5 +1,8 @@
-use cosmwasm_std::{from_slice, Pair, StdError, StdResult, Storage};
-use serde::de::DeserializeOwned;
+use cosmwasm_std::{from_slice, to_vec, Binary, Order, Pair, StdError, StdResult, Storage};
+use cw_storage_plus::{Bound, Prefix};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::keys::KeyDeserialize;
 
 pub type DeserializeFn<T> = fn(&dyn Storage, &[u8], Pair) -> StdResult<Pair<T>>;
 
@@ -10,13 +13,7 @@ pub fn deserialize_multi_kv_custom_pk<T: DeserializeOwned>(
     pk_fn: fn(Vec<u8>) -> Vec<u8>,
 ) -> StdResult<Pair<T>> {
     let (key, pk_len) = kv;
-
-    // Deserialize pk_len
-    let pk_len = from_slice::<u32>(pk_len.as_slice())?;
-
-    // Recover pk from last part of k
-    let offset = key.len() - pk_len as usize;
-    let pk = pk_fn(key[offset..].to_vec());
+    let pk = pk_fn(multi_entry_pk(&key, &pk_len)?.to_vec());
 
     let full_key = namespaces_with_key(&[pk_namespace], pk.as_slice());
 
@@ -28,19 +25,23 @@ pub fn deserialize_multi_kv_custom_pk<T: DeserializeOwned>(
     Ok((pk, v))
 }
 
+/// Recover pk from the last part of a multi index key, using pk length stored as entry value.
+pub(crate) fn multi_entry_pk<'k>(key: &'k [u8], pk_len: &[u8]) -> StdResult<&'k [u8]> {
+    let pk_len = from_slice::<u32>(pk_len)? as usize;
+    if pk_len > key.len() {
+        return Err(StdError::generic_err("Index key is shorter than its pk"));
+    }
+
+    Ok(&key[key.len() - pk_len..])
+}
+
 pub(crate) fn deserialize_multi_kv<T: DeserializeOwned>(
     store: &dyn Storage,
     pk_namespace: &[u8],
     kv: Pair,
 ) -> StdResult<Pair<T>> {
     let (key, pk_len) = kv;
-
-    // Deserialize pk_len
-    let pk_len = from_slice::<u32>(pk_len.as_slice())?;
-
-    // Recover pk from last part of k
-    let offset = key.len() - pk_len as usize;
-    let pk = &key[offset..];
+    let pk = multi_entry_pk(&key, &pk_len)?;
 
     let full_key = namespaces_with_key(&[pk_namespace], pk);
 
@@ -52,6 +53,30 @@ pub(crate) fn deserialize_multi_kv<T: DeserializeOwned>(
     Ok((pk.into(), v))
 }
 
+/// Same layout as `cw-storage-plus` unique index entries, which type is not exported.
+#[derive(Deserialize, Serialize)]
+pub(crate) struct UniqueRef<T> {
+    pub pk: Binary,
+    pub value: T,
+}
+
+pub(crate) fn deserialize_unique_kv<T: DeserializeOwned>(
+    _store: &dyn Storage,
+    _pk_namespace: &[u8],
+    kv: Pair,
+) -> StdResult<Pair<T>> {
+    let (_, v) = kv;
+    let t = from_slice::<UniqueRef<T>>(&v)?;
+    Ok((t.pk.into(), t.value))
+}
+
+pub(crate) fn deserialize_key_kv<K: KeyDeserialize, T>(
+    kv: StdResult<Pair<T>>,
+) -> StdResult<(K::Output, T)> {
+    let (k, v) = kv?;
+    Ok((K::from_vec(k)?, v))
+}
+
 pub(crate) fn encode_length(namespace: &[u8]) -> [u8; 2] {
     if namespace.len() > 0xFFFF {
         panic!("only supports namespaces up to length 0xFFFF")
@@ -74,3 +99,91 @@ pub(crate) fn namespaces_with_key(namespaces: &[&[u8]], key: &[u8]) -> Vec<u8> {
     out.extend_from_slice(key);
     out
 }
+
+/// Returns a new vec of same length and last byte incremented by one, handling overflow up the
+/// chain. Every key prefixed by `input` is lower than the result.
+pub(crate) fn namespace_upper_bound(input: &[u8]) -> Vec<u8> {
+    let mut copy = input.to_vec();
+    // zero out all trailing 255, increment first that is not such
+    for i in (0..input.len()).rev() {
+        if copy[i] == 255 {
+            copy[i] = 0;
+        } else {
+            copy[i] += 1;
+            break;
+        }
+    }
+    copy
+}
+
+/// Namespace keeping the height up to which the changelog of each snapshot key was pruned,
+/// `{len}:{changelog}#pruned` like the length-prefixed parent of `NamespaceBuilder`, so it can
+/// not be the namespace or changelog of another declared type.
+pub(crate) fn pruned_namespace(changelog: &str) -> String {
+    format!("{}:{}#pruned", changelog.len(), changelog)
+}
+
+fn pruned_key(changelog: &[u8], key: &[&[u8]]) -> Vec<u8> {
+    let pruned = [
+        format!("{}:", changelog.len()).as_bytes(),
+        changelog,
+        b"#pruned",
+    ]
+    .concat();
+    namespaces_with_key(&[&[pruned.as_slice()], key].concat(), b"")
+}
+
+/// Fail if the changelog of one snapshot key was pruned above `height`, as history of that
+/// height is lost.
+pub(crate) fn assert_not_pruned(
+    store: &dyn Storage,
+    changelog: &[u8],
+    key: &[&[u8]],
+    height: u64,
+) -> StdResult<()> {
+    let pruned = store
+        .get(&pruned_key(changelog, key))
+        .map(|v| from_slice::<u64>(&v))
+        .transpose()?;
+    match pruned {
+        Some(pruned) if height < pruned => Err(StdError::generic_err(format!(
+            "History before height {} was pruned",
+            pruned
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Remove changelog entries of one snapshot key written before `height`, returns the number of
+/// removed entries. The highest pruned height is kept for `assert_not_pruned`.
+pub(crate) fn prune_changelog(
+    store: &mut dyn Storage,
+    changelog: &[u8],
+    key: &[&[u8]],
+    height: u64,
+) -> StdResult<usize> {
+    // changelog entries are keyed by (key, height), height being the big endian trailing part
+    let heights = Prefix::<Binary>::new(changelog, key)
+        .keys(
+            store,
+            None,
+            Some(Bound::exclusive_int(height)),
+            Order::Ascending,
+        )
+        .collect::<Vec<_>>();
+
+    let namespaces = [&[changelog], key].concat();
+    for h in heights.iter() {
+        store.remove(&namespaces_with_key(&namespaces, h));
+    }
+
+    let pruned = pruned_key(changelog, key);
+    let prev = store
+        .get(&pruned)
+        .map(|v| from_slice::<u64>(&v))
+        .transpose()?;
+    if prev < Some(height) {
+        store.set(&pruned, &to_vec(&height)?);
+    }
+    Ok(heights.len())
+}
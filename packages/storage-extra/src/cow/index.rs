@@ -0,0 +1,376 @@
+use cosmwasm_std::{from_slice, to_vec, Binary, StdResult, Storage};
+use cw_storage_plus::{Index, Prefix, Prefixer, PrimaryKey};
+use serde::{de::DeserializeOwned, Serialize};
+use std::borrow::Cow;
+
+use super::conditional_multi_index::ConditionalMultiIndex;
+use super::custom_dese_index::CustomDeseMultiIndex;
+use super::error::UniqueViolation;
+use super::helpers::{namespaces_with_key, DeserializeFn, UniqueRef};
+use super::indexed_map::{MultiIndexCow, UniqueIndexCow};
+use super::keys::KeyDeserialize;
+use super::prefix::PrefixDe;
+
+/// Namespaces, condition and deserializer shared by every index in this module.
+///
+/// Finish with `multi` or `unique` to get the index, e.g. a unique index with a condition.
+#[derive(Clone)]
+pub struct IndexBuilder<'a, T> {
+    pub(crate) pk_namespace: Cow<'a, str>,
+    pub(crate) idx_namespace: Cow<'a, str>,
+    pub(crate) cond_fn: Option<fn(&T) -> bool>,
+    pub(crate) dese_fn: Option<DeserializeFn<T>>,
+}
+
+impl<'a, T> IndexBuilder<'a, T> {
+    pub const fn new_ref(pk_namespace: &'a str, idx_namespace: &'a str) -> Self {
+        Self {
+            pk_namespace: Cow::Borrowed(pk_namespace),
+            idx_namespace: Cow::Borrowed(idx_namespace),
+            cond_fn: None,
+            dese_fn: None,
+        }
+    }
+
+    pub const fn new_owned(pk_namespace: String, idx_namespace: String) -> Self {
+        Self {
+            pk_namespace: Cow::Owned(pk_namespace),
+            idx_namespace: Cow::Owned(idx_namespace),
+            cond_fn: None,
+            dese_fn: None,
+        }
+    }
+
+    /// Only index data for which `cond_fn` returns `true`.
+    pub const fn with_cond(mut self, cond_fn: fn(&T) -> bool) -> Self {
+        self.cond_fn = Some(cond_fn);
+        self
+    }
+
+    /// Use `dese_fn` instead of the default deserializer when iterating the index.
+    pub const fn with_dese(mut self, dese_fn: DeserializeFn<T>) -> Self {
+        self.dese_fn = Some(dese_fn);
+        self
+    }
+
+    pub const fn multi<K>(self, idx_fn: fn(&T, Vec<u8>) -> K) -> MultiIndexCow<'a, K, T> {
+        MultiIndexCow::from_builder(self, idx_fn)
+    }
+
+    pub const fn unique<K>(self, idx_fn: fn(&T) -> K) -> UniqueIndexCow<'a, K, T> {
+        UniqueIndexCow::from_builder(self, idx_fn)
+    }
+}
+
+impl<T> IndexBuilder<'_, T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+{
+    pub(crate) fn cond(&self, data: &T) -> bool {
+        match self.cond_fn {
+            Some(cond_fn) => cond_fn(data),
+            None => true,
+        }
+    }
+
+    pub(crate) fn raw_key(&self, idx: &[u8]) -> Vec<u8> {
+        namespaces_with_key(&[self.idx_namespace.as_bytes()], idx)
+    }
+
+    pub(crate) fn save_multi(
+        &self,
+        store: &mut dyn Storage,
+        idx: &[u8],
+        pk: &[u8],
+        data: &T,
+    ) -> StdResult<()> {
+        // data that stopped matching must not leave its previous entry behind
+        if self.cond(data) {
+            store.set(&self.raw_key(idx), &to_vec(&(pk.len() as u32))?);
+        } else {
+            store.remove(&self.raw_key(idx));
+        }
+        Ok(())
+    }
+
+    /// Remove regardless of condition, old data may have matched under another condition.
+    pub(crate) fn remove_multi(&self, store: &mut dyn Storage, idx: &[u8]) {
+        store.remove(&self.raw_key(idx));
+    }
+
+    pub(crate) fn unique_entry_pk(&self, value: &[u8]) -> StdResult<Vec<u8>> {
+        Ok(from_slice::<UniqueRef<T>>(value)?.pk.into())
+    }
+
+    /// Whether the copy of data kept by a unique entry differs from `data`.
+    pub(crate) fn unique_entry_is_stale(&self, value: &[u8], data: &T) -> StdResult<bool> {
+        Ok(to_vec(&from_slice::<UniqueRef<T>>(value)?.value)? != to_vec(data)?)
+    }
+
+    pub(crate) fn load_unique(
+        &self,
+        store: &dyn Storage,
+        idx: &[u8],
+    ) -> StdResult<Option<UniqueRef<T>>> {
+        store
+            .get(&self.raw_key(idx))
+            .map(|v| from_slice(&v))
+            .transpose()
+    }
+
+    pub(crate) fn save_unique(
+        &self,
+        store: &mut dyn Storage,
+        idx: &[u8],
+        pk: &[u8],
+        data: &T,
+    ) -> StdResult<()> {
+        if !self.cond(data) {
+            return Ok(());
+        }
+
+        // re-saving the same pk is an upsert, only another owner violates the constraint
+        if let Some(existing) = self.load_unique(store, idx)? {
+            if existing.pk.as_slice() != pk {
+                return Err(UniqueViolation {
+                    index: self.idx_namespace.to_string(),
+                    key: idx.into(),
+                }
+                .into());
+            }
+        }
+
+        store.set(
+            &self.raw_key(idx),
+            &to_vec(&UniqueRef {
+                pk: pk.into(),
+                value: data.clone(),
+            })?,
+        );
+        Ok(())
+    }
+
+    pub(crate) fn remove_unique(
+        &self,
+        store: &mut dyn Storage,
+        idx: &[u8],
+        pk: &[u8],
+    ) -> StdResult<()> {
+        // never drop an entry owned by another pk
+        if let Some(existing) = self.load_unique(store, idx)? {
+            if existing.pk.as_slice() == pk {
+                store.remove(&self.raw_key(idx));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Common interface of `MultiIndexCow`, `UniqueIndexCow`, `CustomDeseMultiIndex` and
+/// `ConditionalMultiIndex`, so they can be used interchangeably.
+pub trait IndexCow<T>: Index<T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+{
+    type Key: for<'key> PrimaryKey<'key>;
+
+    fn pk_namespace(&self) -> &str;
+
+    fn idx_namespace(&self) -> &str;
+
+    /// Deserializer used by `prefix` and `sub_prefix`.
+    fn dese_fn(&self) -> DeserializeFn<T>;
+
+    /// Raw index key of `data` stored under `pk`, `None` if the data is not indexed.
+    fn entry_key(&self, pk: &[u8], data: &T) -> Option<Vec<u8>>;
+
+    /// Raw primary key referenced by a raw index entry.
+    fn entry_pk(&self, key: &[u8], value: &[u8]) -> StdResult<Vec<u8>>;
+
+    /// Whether a raw index entry of `data` keeps outdated data, only unique indexes keep a copy.
+    fn entry_is_stale(&self, _value: &[u8], _data: &T) -> StdResult<bool> {
+        Ok(false)
+    }
+
+    fn index_key(&self, k: Self::Key) -> Vec<u8> {
+        k.joined_key()
+    }
+
+    fn prefix<'k>(&'k self, p: <Self::Key as PrimaryKey<'k>>::Prefix) -> Prefix<T> {
+        Prefix::with_deserialization_function(
+            self.idx_namespace().as_bytes(),
+            &p.prefix(),
+            self.pk_namespace().as_bytes(),
+            self.dese_fn(),
+        )
+    }
+
+    /// Like `prefix`, but with primary keys deserialized through `PK`.
+    fn prefix_de<'k, PK: KeyDeserialize>(
+        &'k self,
+        p: <Self::Key as PrimaryKey<'k>>::Prefix,
+    ) -> PrefixDe<'k, PK, T>
+    where
+        Self: Sized,
+    {
+        let sub_names = p.prefix();
+        let prefix = Prefix::with_deserialization_function(
+            self.idx_namespace().as_bytes(),
+            &sub_names,
+            self.pk_namespace().as_bytes(),
+            self.dese_fn(),
+        );
+        PrefixDe::new(prefix, self, &sub_names)
+    }
+
+    /// Like `sub_prefix`, but with primary keys deserialized through `PK`.
+    fn sub_prefix_de<'k, PK: KeyDeserialize>(
+        &'k self,
+        p: <Self::Key as PrimaryKey<'k>>::SubPrefix,
+    ) -> PrefixDe<'k, PK, T>
+    where
+        Self: Sized,
+    {
+        let sub_names = p.prefix();
+        let prefix = Prefix::with_deserialization_function(
+            self.idx_namespace().as_bytes(),
+            &sub_names,
+            self.pk_namespace().as_bytes(),
+            self.dese_fn(),
+        );
+        PrefixDe::new(prefix, self, &sub_names)
+    }
+
+    fn sub_prefix<'k>(&'k self, p: <Self::Key as PrimaryKey<'k>>::SubPrefix) -> Prefix<T> {
+        Prefix::with_deserialization_function(
+            self.idx_namespace().as_bytes(),
+            &p.prefix(),
+            self.pk_namespace().as_bytes(),
+            self.dese_fn(),
+        )
+    }
+}
+
+// inherent forwards keep callers working without importing `IndexCow`
+macro_rules! impl_inherent_index_cow {
+    ($($index:ident),* $(,)?) => {
+        $(
+            impl<K, T> $index<'_, K, T>
+            where
+                T: Serialize + DeserializeOwned + Clone,
+                K: for<'key> PrimaryKey<'key>,
+            {
+                pub fn index_key(&self, k: K) -> Vec<u8> {
+                    IndexCow::index_key(self, k)
+                }
+
+                pub fn prefix<'k>(&'k self, p: <K as PrimaryKey<'k>>::Prefix) -> Prefix<T> {
+                    IndexCow::prefix(self, p)
+                }
+
+                pub fn sub_prefix<'k>(&'k self, p: <K as PrimaryKey<'k>>::SubPrefix) -> Prefix<T> {
+                    IndexCow::sub_prefix(self, p)
+                }
+
+                pub fn prefix_de<'k, PK: KeyDeserialize>(
+                    &'k self,
+                    p: <K as PrimaryKey<'k>>::Prefix,
+                ) -> PrefixDe<'k, PK, T> {
+                    IndexCow::prefix_de(self, p)
+                }
+
+                pub fn sub_prefix_de<'k, PK: KeyDeserialize>(
+                    &'k self,
+                    p: <K as PrimaryKey<'k>>::SubPrefix,
+                ) -> PrefixDe<'k, PK, T> {
+                    IndexCow::sub_prefix_de(self, p)
+                }
+            }
+        )*
+    };
+}
+
+impl_inherent_index_cow![
+    MultiIndexCow,
+    UniqueIndexCow,
+    CustomDeseMultiIndex,
+    ConditionalMultiIndex,
+];
+
+/// Object safe part of `IndexCow`, so indexes with different keys can be inspected together.
+pub trait IndexInspect<T> {
+    fn namespace(&self) -> &str;
+
+    fn key_of(&self, pk: &[u8], data: &T) -> Option<Vec<u8>>;
+
+    fn pk_of(&self, key: &[u8], value: &[u8]) -> StdResult<Vec<u8>>;
+
+    fn is_stale(&self, value: &[u8], data: &T) -> StdResult<bool>;
+}
+
+impl<T, X> IndexInspect<T> for X
+where
+    T: Serialize + DeserializeOwned + Clone,
+    X: IndexCow<T>,
+{
+    fn namespace(&self) -> &str {
+        self.idx_namespace()
+    }
+
+    fn key_of(&self, pk: &[u8], data: &T) -> Option<Vec<u8>> {
+        self.entry_key(pk, data)
+    }
+
+    fn pk_of(&self, key: &[u8], value: &[u8]) -> StdResult<Vec<u8>> {
+        self.entry_pk(key, value)
+    }
+
+    fn is_stale(&self, value: &[u8], data: &T) -> StdResult<bool> {
+        self.entry_is_stale(value, data)
+    }
+}
+
+/// Like `IndexList`, listing indexes to be checked by `IndexedMapCow::check_indexes`.
+pub trait IndexListInspect<T> {
+    fn get_inspects(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn IndexInspect<T>> + '_>;
+}
+
+/// Resume point of `IndexedMapCow::rebuild_index`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RebuildCursor {
+    /// Raw primary key of the last record saved into the index
+    Records(Vec<u8>),
+    /// Raw index key of the last checked entry, `None` before the first one
+    Entries(Option<Vec<u8>>),
+}
+
+/// Result of `IndexedMapCow::verify_index`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndexReport {
+    /// Namespace of checked index
+    pub index: String,
+    /// Raw primary keys of data without its index entry
+    pub missing: Vec<Binary>,
+    /// Raw index keys which pk is not found in the primary map
+    pub orphaned: Vec<Binary>,
+    /// Raw index keys of existing data, but not matching its current value
+    pub stale: Vec<Binary>,
+}
+
+impl IndexReport {
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.orphaned.is_empty() && self.stale.is_empty()
+    }
+}
+
+/// Result of `IndexedMapCow::check_indexes`, one report per index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConsistencyReport {
+    pub indexes: Vec<IndexReport>,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.indexes.iter().all(IndexReport::is_consistent)
+    }
+}
@@ -1,9 +1,16 @@
-use cosmwasm_std::{Pair, StdError, StdResult, Storage};
-use cw_storage_plus::{Index, IndexList, MultiIndex, Path, Prefix, PrimaryKey, UniqueIndex};
+use cosmwasm_std::{Order, Pair, StdError, StdResult, Storage};
+use cw_storage_plus::{Bound, Index, IndexList, MultiIndex, Path, Prefix, PrimaryKey, UniqueIndex};
 use serde::{de::DeserializeOwned, Serialize};
-use std::{borrow::Cow, marker::PhantomData};
+use std::{any::type_name, borrow::Cow, marker::PhantomData};
 
-use super::indexed_map_ref::IndexedMapRef;
+use super::helpers::{deserialize_multi_kv, deserialize_unique_kv, multi_entry_pk, DeserializeFn};
+use super::index::{
+    ConsistencyReport, IndexBuilder, IndexCow, IndexInspect, IndexListInspect, IndexReport,
+    RebuildCursor,
+};
+use super::indexed_map_ref::{ClearProgress, IndexedMapRef};
+use super::keys::KeyDeserialize;
+use super::sequence::SequenceCow;
 
 #[derive(Debug, Clone)]
 pub struct IndexedMapCow<'a, K, T, I> {
@@ -51,6 +58,18 @@ where
         self.indexed_map().save(store, key, data)
     }
 
+    /// Save `data` under the next id of `seq`, returns the id. The id is only allocated once
+    /// `data` is saved.
+    pub fn push(&'a self, store: &mut dyn Storage, seq: &SequenceCow, data: &T) -> StdResult<u64>
+    where
+        K: From<u64>,
+    {
+        let id = seq.peek_next(store)?;
+        self.save(store, id.into(), data)?;
+        seq.set(store, id)?;
+        Ok(id)
+    }
+
     pub fn remove(&'a self, store: &mut dyn Storage, key: K) -> StdResult<()> {
         self.indexed_map().remove(store, key)
     }
@@ -88,12 +107,107 @@ where
     pub fn sub_prefix(&'a self, p: K::SubPrefix) -> Prefix<T> {
         self.indexed_map().sub_prefix(p)
     }
+
+    pub fn range_raw<'c>(
+        &'a self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<Pair<T>>> + 'c>
+    where
+        T: 'c,
+    {
+        self.indexed_map().range_raw(store, min, max, order)
+    }
+
+    pub fn range<'c>(
+        &'a self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<(K::Output, T)>> + 'c>
+    where
+        T: 'c,
+        K: KeyDeserialize,
+        K::Output: 'c,
+    {
+        self.indexed_map().range(store, min, max, order)
+    }
+
+    pub fn keys<'c>(
+        &'a self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<K::Output>> + 'c>
+    where
+        T: 'c,
+        K: KeyDeserialize,
+        K::Output: 'c,
+    {
+        self.indexed_map().keys(store, min, max, order)
+    }
+
+    pub fn paginate(
+        &'a self,
+        store: &dyn Storage,
+        start_after: Option<K>,
+        limit: usize,
+        order: Order,
+    ) -> StdResult<Vec<(K::Output, T)>>
+    where
+        K: KeyDeserialize,
+    {
+        self.indexed_map()
+            .paginate(store, start_after, limit, order)
+    }
+
+    pub fn clear_prefix(
+        &'a self,
+        store: &mut dyn Storage,
+        p: K::Prefix,
+        limit: usize,
+    ) -> StdResult<ClearProgress> {
+        self.indexed_map().clear_prefix(store, p, limit)
+    }
+
+    pub fn clear(&'a self, store: &mut dyn Storage, limit: usize) -> StdResult<ClearProgress> {
+        self.indexed_map().clear(store, limit)
+    }
+
+    pub fn rebuild_index<X: Index<T> + IndexInspect<T>>(
+        &'a self,
+        store: &mut dyn Storage,
+        index: &X,
+        cursor: Option<RebuildCursor>,
+        limit: usize,
+    ) -> StdResult<Option<RebuildCursor>> {
+        self.indexed_map()
+            .rebuild_index(store, index, cursor, limit)
+    }
+
+    pub fn verify_index<X: IndexInspect<T>>(
+        &'a self,
+        store: &dyn Storage,
+        index: &X,
+    ) -> StdResult<IndexReport> {
+        self.indexed_map().verify_index(store, index)
+    }
+
+    pub fn check_indexes(&'a self, store: &dyn Storage) -> StdResult<ConsistencyReport>
+    where
+        I: IndexListInspect<T>,
+    {
+        self.indexed_map().check_indexes(store)
+    }
 }
 
 #[derive(Clone)]
 pub struct MultiIndexCow<'a, K, T> {
-    pub(crate) pk_namespace: Cow<'a, str>,
-    pub(crate) idx_namespace: Cow<'a, str>,
+    base: IndexBuilder<'a, T>,
     idx_fn: fn(&T, Vec<u8>) -> K,
 }
 
@@ -103,11 +217,7 @@ impl<'k, K, T> MultiIndexCow<'k, K, T> {
         idx_namespace: &'k str,
         idx_fn: fn(&T, Vec<u8>) -> K,
     ) -> Self {
-        Self {
-            idx_fn,
-            pk_namespace: Cow::Borrowed(pk_namespace),
-            idx_namespace: Cow::Borrowed(idx_namespace),
-        }
+        IndexBuilder::new_ref(pk_namespace, idx_namespace).multi(idx_fn)
     }
 
     pub const fn new_owned(
@@ -115,11 +225,14 @@ impl<'k, K, T> MultiIndexCow<'k, K, T> {
         idx_namespace: String,
         idx_fn: fn(&T, Vec<u8>) -> K,
     ) -> Self {
-        Self {
-            idx_fn,
-            pk_namespace: Cow::Owned(pk_namespace),
-            idx_namespace: Cow::Owned(idx_namespace),
-        }
+        IndexBuilder::new_owned(pk_namespace, idx_namespace).multi(idx_fn)
+    }
+
+    pub(crate) const fn from_builder(
+        base: IndexBuilder<'k, T>,
+        idx_fn: fn(&T, Vec<u8>) -> K,
+    ) -> Self {
+        Self { base, idx_fn }
     }
 }
 
@@ -128,56 +241,80 @@ where
     T: Serialize + DeserializeOwned + Clone,
     K: for<'key> PrimaryKey<'key>,
 {
+    /// Upstream `MultiIndex` over the same namespaces, ignoring condition and deserializer.
     pub fn multi_index(&self) -> MultiIndex<K, T> {
-        MultiIndex::new(self.idx_fn, &self.pk_namespace, &self.idx_namespace)
-    }
-
-    pub fn prefix<'key>(&'key self, p: <K as PrimaryKey<'key>>::Prefix) -> Prefix<T> {
-        self.multi_index().prefix(p)
+        MultiIndex::new(
+            self.idx_fn,
+            &self.base.pk_namespace,
+            &self.base.idx_namespace,
+        )
     }
+}
 
-    pub fn sub_prefix<'key>(&'key self, p: <K as PrimaryKey<'key>>::SubPrefix) -> Prefix<T> {
-        self.multi_index().sub_prefix(p)
+impl<K, T> Index<T> for MultiIndexCow<'_, K, T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+    K: for<'key> PrimaryKey<'key>,
+{
+    fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &T) -> StdResult<()> {
+        let idx = (self.idx_fn)(data, pk.to_vec()).joined_key();
+        self.base.save_multi(store, &idx, pk, data)
     }
 
-    pub fn index_key(&self, k: K) -> Vec<u8> {
-        self.multi_index().index_key(k)
+    fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &T) -> StdResult<()> {
+        let idx = (self.idx_fn)(old_data, pk.to_vec()).joined_key();
+        self.base.remove_multi(store, &idx);
+        Ok(())
     }
 }
 
-impl<K, T> Index<T> for MultiIndexCow<'_, K, T>
+impl<K, T> IndexCow<T> for MultiIndexCow<'_, K, T>
 where
     T: Serialize + DeserializeOwned + Clone,
     K: for<'key> PrimaryKey<'key>,
 {
-    fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &T) -> StdResult<()> {
-        self.multi_index().save(store, pk, data)
+    type Key = K;
+
+    fn pk_namespace(&self) -> &str {
+        &self.base.pk_namespace
     }
 
-    fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &T) -> StdResult<()> {
-        self.multi_index().remove(store, pk, old_data)
+    fn idx_namespace(&self) -> &str {
+        &self.base.idx_namespace
+    }
+
+    fn dese_fn(&self) -> DeserializeFn<T> {
+        self.base.dese_fn.unwrap_or(deserialize_multi_kv)
+    }
+
+    fn entry_key(&self, pk: &[u8], data: &T) -> Option<Vec<u8>> {
+        self.base
+            .cond(data)
+            .then(|| (self.idx_fn)(data, pk.to_vec()).joined_key())
+    }
+
+    fn entry_pk(&self, key: &[u8], value: &[u8]) -> StdResult<Vec<u8>> {
+        Ok(multi_entry_pk(key, value)?.to_vec())
     }
 }
 
 #[derive(Clone)]
 pub struct UniqueIndexCow<'a, K, T> {
-    pub(crate) idx_namespace: Cow<'a, str>,
+    base: IndexBuilder<'a, T>,
     idx_fn: fn(&T) -> K,
 }
 
 impl<'k, K, T> UniqueIndexCow<'k, K, T> {
     pub const fn new_ref(idx_namespace: &'k str, idx_fn: fn(&T) -> K) -> Self {
-        Self {
-            idx_fn,
-            idx_namespace: Cow::Borrowed(idx_namespace),
-        }
+        IndexBuilder::new_ref("", idx_namespace).unique(idx_fn)
     }
 
     pub const fn new_owned(idx_namespace: String, idx_fn: fn(&T) -> K) -> Self {
-        Self {
-            idx_fn,
-            idx_namespace: Cow::Owned(idx_namespace),
-        }
+        IndexBuilder::new_owned(String::new(), idx_namespace).unique(idx_fn)
+    }
+
+    pub(crate) const fn from_builder(base: IndexBuilder<'k, T>, idx_fn: fn(&T) -> K) -> Self {
+        Self { base, idx_fn }
     }
 }
 
@@ -186,24 +323,27 @@ where
     T: Serialize + DeserializeOwned + Clone,
     K: for<'key> PrimaryKey<'key>,
 {
+    /// Upstream `UniqueIndex` over the same namespace, ignoring condition and deserializer.
     pub fn unique_index(&self) -> UniqueIndex<K, T> {
-        UniqueIndex::new(self.idx_fn, &self.idx_namespace)
-    }
-
-    pub fn index_key(&self, k: K) -> Vec<u8> {
-        self.unique_index().index_key(k)
+        UniqueIndex::new(self.idx_fn, &self.base.idx_namespace)
     }
 
-    pub fn prefix<'key>(&'key self, p: <K as PrimaryKey<'key>>::Prefix) -> Prefix<T> {
-        self.unique_index().prefix(p)
+    pub fn item(&self, store: &dyn Storage, idx: K) -> StdResult<Option<Pair<T>>> {
+        self.may_load_by(store, idx)
     }
 
-    pub fn sub_prefix<'key>(&'key self, p: <K as PrimaryKey<'key>>::SubPrefix) -> Prefix<T> {
-        self.unique_index().sub_prefix(p)
+    /// Load primary key and value of the entry owning `idx`.
+    pub fn load_by(&self, store: &dyn Storage, idx: K) -> StdResult<Pair<T>> {
+        self.may_load_by(store, idx)?
+            .ok_or_else(|| StdError::not_found(type_name::<T>()))
     }
 
-    pub fn item(&self, store: &dyn Storage, idx: K) -> StdResult<Option<Pair<T>>> {
-        self.unique_index().item(store, idx)
+    /// Load primary key and value of the entry owning `idx`, if any.
+    pub fn may_load_by(&self, store: &dyn Storage, idx: K) -> StdResult<Option<Pair<T>>> {
+        Ok(self
+            .base
+            .load_unique(store, &idx.joined_key())?
+            .map(|r| (r.pk.into(), r.value)))
     }
 }
 
@@ -213,17 +353,54 @@ where
     K: for<'key> PrimaryKey<'key>,
 {
     fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &T) -> StdResult<()> {
-        self.unique_index().save(store, pk, data)
+        let idx = (self.idx_fn)(data).joined_key();
+        self.base.save_unique(store, &idx, pk, data)
     }
 
     fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &T) -> StdResult<()> {
-        self.unique_index().remove(store, pk, old_data)
+        let idx = (self.idx_fn)(old_data).joined_key();
+        self.base.remove_unique(store, &idx, pk)
+    }
+}
+
+impl<K, T> IndexCow<T> for UniqueIndexCow<'_, K, T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+    K: for<'key> PrimaryKey<'key>,
+{
+    type Key = K;
+
+    fn pk_namespace(&self) -> &str {
+        &self.base.pk_namespace
+    }
+
+    fn idx_namespace(&self) -> &str {
+        &self.base.idx_namespace
+    }
+
+    fn dese_fn(&self) -> DeserializeFn<T> {
+        self.base.dese_fn.unwrap_or(deserialize_unique_kv)
+    }
+
+    fn entry_key(&self, _pk: &[u8], data: &T) -> Option<Vec<u8>> {
+        self.base
+            .cond(data)
+            .then(|| (self.idx_fn)(data).joined_key())
+    }
+
+    fn entry_pk(&self, _key: &[u8], value: &[u8]) -> StdResult<Vec<u8>> {
+        self.base.unique_entry_pk(value)
+    }
+
+    fn entry_is_stale(&self, value: &[u8], data: &T) -> StdResult<bool> {
+        self.base.unique_entry_is_stale(value, data)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::cow::UniqueViolation;
 
     use cosmwasm_std::{testing::MockStorage, Addr, Order};
     use cw_storage_plus::U64Key;
@@ -402,4 +579,121 @@ mod test {
             None
         );
     }
+
+    #[test]
+    fn unique_violation() {
+        let mut storage = MockStorage::new();
+        const TO: IndexedMapCow<U64Key, ToIndex, ToIndexList> = IndexedMapCow::new_ref(
+            "primary",
+            ToIndexList {
+                count: MultiIndexCow::new_ref("primary", "primary_count", |e, k| {
+                    (e.count.into(), k)
+                }),
+                address: UniqueIndexCow::new_ref("primary_address", |e| e.address.clone()),
+            },
+        );
+
+        let first = ToIndex {
+            id: 0,
+            count: 5,
+            address: Addr::unchecked("a"),
+        };
+        let second = ToIndex {
+            id: 1,
+            count: 7,
+            address: Addr::unchecked("b"),
+        };
+
+        TO.save(&mut storage, first.id.into(), &first).unwrap();
+        TO.save(&mut storage, second.id.into(), &second).unwrap();
+
+        // upsert of the same pk keeps its unique entry
+        TO.save(&mut storage, first.id.into(), &first).unwrap();
+        assert_eq!(
+            TO.index
+                .address
+                .load_by(&storage, Addr::unchecked("a"))
+                .unwrap(),
+            (U64Key::new(0).joined_key(), first.clone())
+        );
+
+        // moving second onto address of first is rejected with a typed error
+        let err = TO
+            .update(&mut storage, second.id.into(), |e| -> StdResult<_> {
+                Ok(ToIndex {
+                    count: 9,
+                    address: Addr::unchecked("a"),
+                    ..e.unwrap()
+                })
+            })
+            .unwrap_err();
+        assert_eq!(
+            UniqueViolation::from_std_error(&err),
+            Some(UniqueViolation {
+                index: "primary_address".to_string(),
+                key: Addr::unchecked("a").joined_key().into(),
+            })
+        );
+
+        // nothing of the failed upsert is left behind
+        assert_eq!(TO.load(&storage, second.id.into()).unwrap(), second);
+        assert_eq!(
+            TO.index
+                .address
+                .may_load_by(&storage, Addr::unchecked("b"))
+                .unwrap(),
+            Some((U64Key::new(1).joined_key(), second.clone()))
+        );
+        assert_eq!(
+            TO.index
+                .address
+                .load_by(&storage, Addr::unchecked("a"))
+                .unwrap()
+                .1,
+            first
+        );
+        assert_eq!(
+            TO.index
+                .count
+                .prefix(9.into())
+                .range(&storage, None, None, Order::Ascending)
+                .count(),
+            0
+        );
+        assert_eq!(
+            TO.index
+                .count
+                .prefix(7.into())
+                .range(&storage, None, None, Order::Ascending)
+                .map(|e| e.unwrap().1)
+                .collect::<Vec<_>>(),
+            vec![second]
+        );
+
+        // changing only the unique field of an entry is revalidated and moves its index
+        let moved = ToIndex {
+            address: Addr::unchecked("c"),
+            ..first.clone()
+        };
+        TO.save(&mut storage, first.id.into(), &moved).unwrap();
+        assert_eq!(
+            TO.index
+                .address
+                .may_load_by(&storage, Addr::unchecked("a"))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            TO.index
+                .address
+                .load_by(&storage, Addr::unchecked("c"))
+                .unwrap()
+                .1,
+            moved
+        );
+        TO.index
+            .address
+            .load_by(&storage, Addr::unchecked("d"))
+            .unwrap_err();
+    }
 }
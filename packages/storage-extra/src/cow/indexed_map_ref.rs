@@ -3,10 +3,25 @@
 ///
 /// Modified from:
 /// https://github.com/CosmWasm/cw-plus/blob/v0.9.1/packages/storage-plus/src/indexed_map.rs
-use cosmwasm_std::{StdError, StdResult, Storage};
-use cw_storage_plus::{IndexList, Map, Path, Prefix, Prefixer, PrimaryKey};
+use cosmwasm_std::{from_slice, Order, Pair, StdError, StdResult, Storage};
+use cw_storage_plus::{
+    range_with_prefix, Bound, Index, IndexList, Map, Path, Prefix, Prefixer, PrimaryKey,
+};
 use serde::{de::DeserializeOwned, Serialize};
 
+use super::helpers::{deserialize_key_kv, namespace_upper_bound, namespaces_with_key};
+use super::index::{ConsistencyReport, IndexInspect, IndexListInspect, IndexReport, RebuildCursor};
+use super::keys::KeyDeserialize;
+
+/// Result of a `clear` batch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClearProgress {
+    /// Records removed by this batch
+    pub removed: usize,
+    /// No record is left, otherwise call again in another transaction
+    pub done: bool,
+}
+
 pub struct IndexedMapRef<'a, K, T, I> {
     pk_namespace: &'a [u8],
     primary: Map<'a, K, T>,
@@ -57,8 +72,11 @@ where
             }
         }
         if let Some(updated) = data {
-            for index in self.idx.get_indexes() {
-                index.save(store, &pk, updated)?;
+            for (i, index) in self.idx.get_indexes().enumerate() {
+                if let Err(err) = index.save(store, &pk, updated) {
+                    self.rollback(store, &pk, updated, i, old_data)?;
+                    return Err(err);
+                }
             }
             self.primary.save(store, key, updated)?;
         } else {
@@ -67,6 +85,26 @@ where
         Ok(())
     }
 
+    /// Undo a failed `replace`, dropping the first `saved` new index entries and restoring old ones.
+    fn rollback(
+        &self,
+        store: &mut dyn Storage,
+        pk: &[u8],
+        updated: &T,
+        saved: usize,
+        old_data: Option<&T>,
+    ) -> StdResult<()> {
+        for index in self.idx.get_indexes().take(saved) {
+            index.remove(store, pk, updated)?;
+        }
+        if let Some(old) = old_data {
+            for index in self.idx.get_indexes() {
+                index.save(store, pk, old)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn update<A, E>(&self, store: &mut dyn Storage, key: K, action: A) -> Result<T, E>
     where
         A: FnOnce(Option<T>) -> Result<T, E>,
@@ -94,4 +132,297 @@ where
     pub fn sub_prefix(&self, p: K::SubPrefix) -> Prefix<T> {
         Prefix::new(self.pk_namespace, &p.prefix())
     }
+
+    pub fn range_raw<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<Pair<T>>> + 'c>
+    where
+        T: 'c,
+    {
+        Prefix::new(self.pk_namespace, &[]).range(store, min, max, order)
+    }
+
+    /// Range through the primary namespace, bounds are raw joined keys.
+    pub fn range<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<(K::Output, T)>> + 'c>
+    where
+        T: 'c,
+        K: KeyDeserialize,
+        K::Output: 'c,
+    {
+        let de_fn: fn(_) -> _ = deserialize_key_kv::<K, T>;
+        Box::new(self.range_raw(store, min, max, order).map(de_fn))
+    }
+
+    pub fn keys<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<K::Output>> + 'c>
+    where
+        T: 'c,
+        K: KeyDeserialize,
+        K::Output: 'c,
+    {
+        let de_fn: fn(_) -> _ = K::from_vec;
+        Box::new(
+            Prefix::<T>::new(self.pk_namespace, &[])
+                .keys(store, min, max, order)
+                .map(de_fn),
+        )
+    }
+
+    /// Load at most `limit` records after `start_after` key, in given order.
+    pub fn paginate(
+        &self,
+        store: &dyn Storage,
+        start_after: Option<K>,
+        limit: usize,
+        order: Order,
+    ) -> StdResult<Vec<(K::Output, T)>>
+    where
+        K: KeyDeserialize,
+    {
+        let bound = start_after.map(|k| Bound::Exclusive(k.joined_key()));
+        let (min, max) = match order {
+            Order::Ascending => (bound, None),
+            Order::Descending => (None, bound),
+        };
+
+        self.range(store, min, max, order).take(limit).collect()
+    }
+
+    /// Remove at most `limit` records under prefix `p`, together with their index entries.
+    ///
+    /// Returns how many records were removed and whether none is left under the prefix, so a
+    /// large cleanup can be continued in another transaction. Fails on zero `limit`.
+    pub fn clear_prefix(
+        &self,
+        store: &mut dyn Storage,
+        p: K::Prefix,
+        limit: usize,
+    ) -> StdResult<ClearProgress> {
+        self.clear_raw(store, &p.prefix(), limit)
+    }
+
+    /// Remove at most `limit` records, together with their index entries.
+    ///
+    /// Returns how many records were removed and whether the map is empty.
+    pub fn clear(&self, store: &mut dyn Storage, limit: usize) -> StdResult<ClearProgress> {
+        self.clear_raw(store, &[], limit)
+    }
+
+    /// Repair `index` in batches of at most `limit` records or entries, e.g. to backfill an index
+    /// added to a map which already holds data, or after its key function or condition changed.
+    ///
+    /// Every record is saved into `index` first, then entries whose pk is not found or whose key
+    /// does not match current data anymore are removed. Start with `None` and pass the returned
+    /// cursor in another transaction, until `None` is returned once done. Fails on zero `limit`,
+    /// which would never make progress.
+    pub fn rebuild_index<X: Index<T> + IndexInspect<T>>(
+        &self,
+        store: &mut dyn Storage,
+        index: &X,
+        cursor: Option<RebuildCursor>,
+        limit: usize,
+    ) -> StdResult<Option<RebuildCursor>> {
+        if limit == 0 {
+            return Err(StdError::generic_err(
+                "Rebuild limit must be greater than zero",
+            ));
+        }
+
+        match cursor {
+            None => self.rebuild_records(store, index, None, limit),
+            Some(RebuildCursor::Records(pk)) => self.rebuild_records(store, index, Some(pk), limit),
+            Some(RebuildCursor::Entries(key)) => self.rebuild_entries(store, index, key, limit),
+        }
+    }
+
+    fn rebuild_records<X: Index<T>>(
+        &self,
+        store: &mut dyn Storage,
+        index: &X,
+        start_after: Option<Vec<u8>>,
+        limit: usize,
+    ) -> StdResult<Option<RebuildCursor>> {
+        let mut records = Prefix::<T>::new(self.pk_namespace, &[])
+            .range(
+                store,
+                start_after.map(Bound::Exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit.saturating_add(1))
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let has_more = records.len() > limit;
+        records.truncate(limit);
+
+        for (pk, data) in &records {
+            index.save(store, pk, data)?;
+        }
+
+        Ok(match records.pop() {
+            Some((pk, _)) if has_more => Some(RebuildCursor::Records(pk)),
+            _ => Some(RebuildCursor::Entries(None)),
+        })
+    }
+
+    fn rebuild_entries<X: Index<T> + IndexInspect<T>>(
+        &self,
+        store: &mut dyn Storage,
+        index: &X,
+        start_after: Option<Vec<u8>>,
+        limit: usize,
+    ) -> StdResult<Option<RebuildCursor>> {
+        let idx_namespace = index.namespace().as_bytes();
+        let mut entries = range_with_prefix(
+            store,
+            &namespaces_with_key(&[idx_namespace], b""),
+            start_after.map(Bound::Exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit.saturating_add(1))
+        .collect::<Vec<_>>();
+
+        let has_more = entries.len() > limit;
+        entries.truncate(limit);
+
+        for (key, value) in &entries {
+            let pk = index.pk_of(key, value)?;
+            let data = match store.get(&namespaces_with_key(&[self.pk_namespace], &pk)) {
+                Some(v) => from_slice::<T>(&v)?,
+                None => {
+                    store.remove(&namespaces_with_key(&[idx_namespace], key));
+                    continue;
+                }
+            };
+
+            if index.key_of(&pk, &data).as_deref() != Some(key.as_slice()) {
+                store.remove(&namespaces_with_key(&[idx_namespace], key));
+            } else if index.is_stale(value, &data)? {
+                index.save(store, &pk, &data)?;
+            }
+        }
+
+        Ok(match entries.pop() {
+            Some((key, _)) if has_more => Some(RebuildCursor::Entries(Some(key))),
+            _ => None,
+        })
+    }
+
+    /// Compare `index` with the whole primary map, reporting records missing from the index and
+    /// orphaned or stale index entries. Meant for queries and migration checks.
+    pub fn verify_index<X: IndexInspect<T>>(
+        &self,
+        store: &dyn Storage,
+        index: &X,
+    ) -> StdResult<IndexReport> {
+        self.inspect(store, index)
+    }
+
+    /// Run `verify_index` on every index of `I`.
+    pub fn check_indexes(&self, store: &dyn Storage) -> StdResult<ConsistencyReport>
+    where
+        I: IndexListInspect<T>,
+    {
+        Ok(ConsistencyReport {
+            indexes: self
+                .idx
+                .get_inspects()
+                .map(|index| self.inspect(store, index))
+                .collect::<StdResult<_>>()?,
+        })
+    }
+
+    fn inspect(&self, store: &dyn Storage, index: &dyn IndexInspect<T>) -> StdResult<IndexReport> {
+        let mut report = IndexReport {
+            index: index.namespace().to_string(),
+            ..IndexReport::default()
+        };
+        let idx_namespace = index.namespace().as_bytes();
+
+        for record in
+            Prefix::<T>::new(self.pk_namespace, &[]).range(store, None, None, Order::Ascending)
+        {
+            let (pk, data) = record?;
+            if let Some(key) = index.key_of(&pk, &data) {
+                let owned = match store.get(&namespaces_with_key(&[idx_namespace], &key)) {
+                    Some(value) => index.pk_of(&key, &value)? == pk,
+                    None => false,
+                };
+                if !owned {
+                    report.missing.push(pk.into());
+                }
+            }
+        }
+
+        let start = namespaces_with_key(&[idx_namespace], b"");
+        let end = namespace_upper_bound(&start);
+        for (k, value) in store.range(Some(&start), Some(&end), Order::Ascending) {
+            let key = &k[start.len()..];
+            let pk = index.pk_of(key, &value)?;
+            let data = match store.get(&namespaces_with_key(&[self.pk_namespace], &pk)) {
+                Some(v) => from_slice::<T>(&v)?,
+                None => {
+                    report.orphaned.push(key.into());
+                    continue;
+                }
+            };
+
+            if index.key_of(&pk, &data).as_deref() != Some(key) || index.is_stale(&value, &data)? {
+                report.stale.push(key.into());
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn clear_raw(
+        &self,
+        store: &mut dyn Storage,
+        prefix: &[&[u8]],
+        limit: usize,
+    ) -> StdResult<ClearProgress> {
+        if limit == 0 {
+            return Err(StdError::generic_err(
+                "Clear limit must be greater than zero",
+            ));
+        }
+
+        // one record past `limit` tells whether more are left, without reading the whole prefix
+        let mut records = Prefix::<T>::new(self.pk_namespace, prefix)
+            .range(store, None, None, Order::Ascending)
+            .take(limit.saturating_add(1))
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let done = records.len() <= limit;
+        records.truncate(limit);
+
+        for (k, old) in &records {
+            let pk = namespaces_with_key(prefix, k);
+            for index in self.idx.get_indexes() {
+                index.remove(store, &pk, old)?;
+            }
+            store.remove(&namespaces_with_key(&[self.pk_namespace], &pk));
+        }
+
+        Ok(ClearProgress {
+            removed: records.len(),
+            done,
+        })
+    }
 }
@@ -0,0 +1,235 @@
+use cosmwasm_std::{Addr, StdError, StdResult};
+use cw_storage_plus::{
+    Bound, Endian, I128Key, I16Key, I32Key, I64Key, I8Key, Prefixer, U128Key, U16Key, U32Key,
+    U64Key, U8Key,
+};
+
+use super::helpers::{namespace_upper_bound, namespaces_with_key};
+
+/// Recover typed key from raw storage key bytes, which `cw-storage-plus` doesn't provide yet.
+///
+/// Tuple keys expect every element but the last one to be a single length-prefixed segment,
+/// like layout produced by `PrimaryKey::joined_key`.
+pub trait KeyDeserialize {
+    type Output: Sized;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output>;
+
+    fn from_slice(value: &[u8]) -> StdResult<Self::Output> {
+        Self::from_vec(value.to_vec())
+    }
+}
+
+impl KeyDeserialize for () {
+    type Output = ();
+
+    fn from_vec(_value: Vec<u8>) -> StdResult<Self::Output> {
+        Ok(())
+    }
+}
+
+impl KeyDeserialize for Vec<u8> {
+    type Output = Vec<u8>;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+        Ok(value)
+    }
+}
+
+impl KeyDeserialize for &[u8] {
+    type Output = Vec<u8>;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+        Ok(value)
+    }
+}
+
+impl KeyDeserialize for String {
+    type Output = String;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+        String::from_utf8(value).map_err(|e| StdError::invalid_utf8(e.to_string()))
+    }
+}
+
+impl KeyDeserialize for &str {
+    type Output = String;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+        String::from_vec(value)
+    }
+}
+
+impl KeyDeserialize for Addr {
+    type Output = Addr;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+        Ok(Addr::unchecked(String::from_vec(value)?))
+    }
+}
+
+impl KeyDeserialize for &Addr {
+    type Output = Addr;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+        Addr::from_vec(value)
+    }
+}
+
+fn int_from_vec<T: Endian>(value: Vec<u8>) -> StdResult<T> {
+    let mut buf = T::Buf::default();
+    if buf.as_ref().len() != value.len() {
+        return Err(StdError::generic_err(format!(
+            "Invalid integer key length, expected {} but found {}",
+            buf.as_ref().len(),
+            value.len()
+        )));
+    }
+    buf.as_mut().copy_from_slice(&value);
+
+    Ok(T::from_be_bytes(buf))
+}
+
+// `IntKey` itself is not exported, implement for every alias instead
+macro_rules! impl_int_key_deserialize {
+    ($($key:ty => $int:ty),* $(,)?) => {
+        $(
+            impl KeyDeserialize for $key {
+                type Output = $int;
+
+                fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+                    int_from_vec::<$int>(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_int_key_deserialize![
+    U8Key => u8,
+    U16Key => u16,
+    U32Key => u32,
+    U64Key => u64,
+    U128Key => u128,
+    I8Key => i8,
+    I16Key => i16,
+    I32Key => i32,
+    I64Key => i64,
+    I128Key => i128,
+];
+
+impl<T: KeyDeserialize, U: KeyDeserialize> KeyDeserialize for (T, U) {
+    type Output = (T::Output, U::Output);
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+        let (t, rest) = split_first_key(&value)?;
+
+        Ok((T::from_slice(t)?, U::from_slice(rest)?))
+    }
+}
+
+impl<T: KeyDeserialize, U: KeyDeserialize, V: KeyDeserialize> KeyDeserialize for (T, U, V) {
+    type Output = (T::Output, U::Output, V::Output);
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+        let (t, rest) = split_first_key(&value)?;
+        let (u, rest) = split_first_key(rest)?;
+
+        Ok((T::from_slice(t)?, U::from_slice(u)?, V::from_slice(rest)?))
+    }
+}
+
+/// Split length-prefixed first segment of a joined key from the rest of it.
+pub(crate) fn split_first_key(value: &[u8]) -> StdResult<(&[u8], &[u8])> {
+    if value.len() < 2 {
+        return Err(StdError::generic_err(
+            "Key is too short to be length-prefixed",
+        ));
+    }
+
+    let len = u16::from_be_bytes([value[0], value[1]]) as usize;
+    if value.len() < len + 2 {
+        return Err(StdError::generic_err(
+            "Key is shorter than its length prefix",
+        ));
+    }
+
+    Ok((&value[2..len + 2], &value[len + 2..]))
+}
+
+/// Bound over prefix of a composite key, used to range through several prefixes at once.
+#[derive(Clone, Debug)]
+pub enum PrefixBound<P> {
+    Inclusive(P),
+    Exclusive(P),
+}
+
+impl<P> PrefixBound<P> {
+    pub fn inclusive<T: Into<P>>(prefix: T) -> Self {
+        PrefixBound::Inclusive(prefix.into())
+    }
+
+    pub fn exclusive<T: Into<P>>(prefix: T) -> Self {
+        PrefixBound::Exclusive(prefix.into())
+    }
+}
+
+impl<'p, P: Prefixer<'p>> PrefixBound<P> {
+    /// Raw lower `Bound` covering every key under this prefix.
+    pub fn to_min_bound(&self) -> Bound {
+        match self {
+            PrefixBound::Inclusive(p) => Bound::Inclusive(joined_prefix(p)),
+            PrefixBound::Exclusive(p) => Bound::Inclusive(namespace_upper_bound(&joined_prefix(p))),
+        }
+    }
+
+    /// Raw upper `Bound` covering every key under this prefix.
+    pub fn to_max_bound(&self) -> Bound {
+        match self {
+            PrefixBound::Inclusive(p) => Bound::Exclusive(namespace_upper_bound(&joined_prefix(p))),
+            PrefixBound::Exclusive(p) => Bound::Exclusive(joined_prefix(p)),
+        }
+    }
+}
+
+fn joined_prefix<'p, P: Prefixer<'p>>(p: &P) -> Vec<u8> {
+    namespaces_with_key(&p.prefix(), b"")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cw_storage_plus::PrimaryKey;
+
+    #[test]
+    fn deserialize_simple_keys() {
+        assert_eq!(
+            <&Addr>::from_vec(b"addr".to_vec()).unwrap(),
+            Addr::unchecked("addr")
+        );
+        assert_eq!(String::from_vec(b"str".to_vec()).unwrap(), "str");
+        assert_eq!(
+            U64Key::from_vec(U64Key::new(42).joined_key()).unwrap(),
+            42u64
+        );
+        U64Key::from_vec(b"short".to_vec()).unwrap_err();
+        String::from_vec(vec![0xff]).unwrap_err();
+    }
+
+    #[test]
+    fn deserialize_tuple_keys() {
+        let key = (Addr::unchecked("a"), U64Key::new(7)).joined_key();
+        assert_eq!(
+            <(Addr, U64Key)>::from_vec(key).unwrap(),
+            (Addr::unchecked("a"), 7)
+        );
+
+        let key = ("b", U32Key::new(1), b"pk".to_vec()).joined_key();
+        assert_eq!(
+            <(&str, U32Key, Vec<u8>)>::from_vec(key).unwrap(),
+            ("b".to_string(), 1, b"pk".to_vec())
+        );
+
+        <(Addr, U64Key)>::from_vec(vec![0, 5, 1]).unwrap_err();
+    }
+}
@@ -1,8 +1,12 @@
-use cosmwasm_std::{StdError, StdResult, Storage};
-use cw_storage_plus::{Map, Prefix, PrimaryKey};
+use cosmwasm_std::{Order, Pair, StdError, StdResult, Storage};
+use cw_storage_plus::{Bound, Map, Prefix, PrimaryKey};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{borrow::Cow, marker::PhantomData};
 
+use super::helpers::{deserialize_key_kv, namespaces_with_key};
+use super::keys::{KeyDeserialize, PrefixBound};
+use super::namespace::NamespaceBuilder;
+
 #[derive(Debug, Clone)]
 pub struct MapCow<'a, K, T> {
     pub(crate) namespace: Cow<'a, str>,
@@ -29,6 +33,26 @@ where
             data_type: PhantomData,
         }
     }
+
+    /// Map of the same types under child namespace `segment`, see `NamespaceBuilder`. Only
+    /// children added through `add_child` are enumerated by `children`.
+    pub fn child(&self, segment: &str) -> MapCow<'a, K, T> {
+        NamespaceBuilder::new(&self.namespace).push(segment).map()
+    }
+
+    /// Namespace recording children added through `add_child`, `{len}:{namespace}#children`.
+    /// The length-prefixed parent keeps it apart from other maps' registries, and `#` from
+    /// namespaces of `child`, whose segments follow a `/`.
+    pub(crate) fn children_namespace(&self) -> String {
+        format!(
+            "{}#children",
+            NamespaceBuilder::new(&self.namespace).build()
+        )
+    }
+
+    fn children_map(&self) -> MapCow<'a, &str, ()> {
+        MapCow::new_owned(self.children_namespace())
+    }
 }
 
 impl<'a, 'key, K, T> MapCow<'a, K, T>
@@ -76,12 +100,167 @@ where
     {
         self.map().update(store, k, action)
     }
+
+    /// Range through the whole map with raw keys.
+    pub fn range_raw<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<Pair<T>>> + 'c>
+    where
+        T: 'c,
+    {
+        Prefix::new(self.namespace.as_bytes(), &[]).range(store, min, max, order)
+    }
+
+    /// Range through the whole map, bounds are raw joined keys.
+    pub fn range<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<(K::Output, T)>> + 'c>
+    where
+        T: 'c,
+        K: KeyDeserialize,
+        K::Output: 'c,
+    {
+        let de_fn: fn(_) -> _ = deserialize_key_kv::<K, T>;
+        Box::new(self.range_raw(store, min, max, order).map(de_fn))
+    }
+
+    pub fn keys<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<K::Output>> + 'c>
+    where
+        T: 'c,
+        K: KeyDeserialize,
+        K::Output: 'c,
+    {
+        let de_fn: fn(_) -> _ = K::from_vec;
+        Box::new(
+            Prefix::<T>::new(self.namespace.as_bytes(), &[])
+                .keys(store, min, max, order)
+                .map(de_fn),
+        )
+    }
+
+    /// Range through every key whose prefix is within given bounds, across prefixes.
+    pub fn prefix_range<'c>(
+        &'key self,
+        store: &'c dyn Storage,
+        min: Option<PrefixBound<K::Prefix>>,
+        max: Option<PrefixBound<K::Prefix>>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<(K::Output, T)>> + 'c>
+    where
+        T: 'c,
+        K: KeyDeserialize,
+        K::Output: 'c,
+    {
+        self.range(
+            store,
+            min.map(|b| b.to_min_bound()),
+            max.map(|b| b.to_max_bound()),
+            order,
+        )
+    }
+
+    /// Record child `segment` to be enumerated and cleared with the parent, and return it.
+    pub fn add_child(&self, store: &mut dyn Storage, segment: &str) -> StdResult<MapCow<'a, K, T>> {
+        self.children_map().save(store, segment, &())?;
+        Ok(self.child(segment))
+    }
+
+    /// Segments of children recorded by `add_child`.
+    pub fn children<'c>(
+        &self,
+        store: &'c dyn Storage,
+        start_after: Option<&str>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<String>> + 'c> {
+        let (min, max) = match order {
+            Order::Ascending => (start_after.map(|s| Bound::exclusive(s.as_bytes())), None),
+            Order::Descending => (None, start_after.map(|s| Bound::exclusive(s.as_bytes()))),
+        };
+        self.children_map().keys(store, min, max, order)
+    }
+
+    /// Remove at most `limit` entries of child `segment`, forgetting the child once empty.
+    ///
+    /// Returns `true` once the child is cleared, otherwise call again in another transaction.
+    /// Children of the child are left as is.
+    pub fn clear_child(
+        &self,
+        store: &mut dyn Storage,
+        segment: &str,
+        limit: usize,
+    ) -> StdResult<bool> {
+        Ok(self.clear_child_entries(store, segment, limit)?.1)
+    }
+
+    /// Remove at most `limit` entries across every child recorded by `add_child`, visiting at
+    /// most `limit` children. Cleared children are forgotten, so each call continues where the
+    /// previous one stopped.
+    ///
+    /// Returns `true` once every child is cleared, otherwise call again in another transaction.
+    pub fn clear_children(&self, store: &mut dyn Storage, limit: usize) -> StdResult<bool> {
+        let segments = self
+            .children(store, None, Order::Ascending)
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut remaining = limit;
+        for segment in segments {
+            let (removed, cleared) = self.clear_child_entries(store, &segment, remaining)?;
+            if !cleared {
+                return Ok(false);
+            }
+            remaining -= removed;
+        }
+        Ok(self
+            .children(store, None, Order::Ascending)
+            .next()
+            .is_none())
+    }
+
+    fn clear_child_entries(
+        &self,
+        store: &mut dyn Storage,
+        segment: &str,
+        limit: usize,
+    ) -> StdResult<(usize, bool)> {
+        let child = self.child(segment);
+        let mut keys = Prefix::<T>::new(child.namespace.as_bytes(), &[])
+            .keys(store, None, None, Order::Ascending)
+            .take(limit + 1)
+            .collect::<Vec<_>>();
+
+        let cleared = keys.len() <= limit;
+        keys.truncate(limit);
+        for key in &keys {
+            store.remove(&namespaces_with_key(&[child.namespace.as_bytes()], key));
+        }
+
+        if cleared {
+            self.children_map().remove(store, segment);
+        }
+        Ok((keys.len(), cleared))
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use cosmwasm_std::{testing::MockStorage, Addr};
+    use cw_storage_plus::U64Key;
 
     #[test]
     fn new_owned() {
@@ -103,4 +282,162 @@ mod test {
         ADDR_REF.map().save(&mut storage, &a, &1).unwrap();
         assert_eq!(ADDR_REF.map().load(&storage, &a).unwrap(), 1);
     }
+
+    #[test]
+    fn range_typed_keys() {
+        let mut storage = MockStorage::new();
+        let map: MapCow<&Addr, u64> = MapCow::new_owned(String::from("g"));
+
+        let a = Addr::unchecked("a");
+        let b = Addr::unchecked("b");
+        let c = Addr::unchecked("c");
+
+        map.save(&mut storage, &a, &1).unwrap();
+        map.save(&mut storage, &b, &2).unwrap();
+        map.save(&mut storage, &c, &3).unwrap();
+
+        assert_eq!(
+            map.range(&storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()
+                .unwrap(),
+            vec![(a.clone(), 1), (b.clone(), 2), (c.clone(), 3)]
+        );
+
+        assert_eq!(
+            map.keys(
+                &storage,
+                Some(Bound::exclusive(a.as_bytes())),
+                None,
+                Order::Descending
+            )
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap(),
+            vec![c, b.clone()]
+        );
+
+        assert_eq!(
+            map.range_raw(&storage, None, None, Order::Ascending)
+                .next()
+                .unwrap()
+                .unwrap(),
+            (b"a".to_vec(), 1)
+        );
+    }
+
+    #[test]
+    fn prefix_range() {
+        let mut storage = MockStorage::new();
+        const POSITIONS: MapCow<(&str, U64Key), u64> = MapCow::new_ref("positions");
+
+        for (pool, id) in [("a", 1), ("a", 2), ("b", 1), ("c", 5), ("d", 1)] {
+            POSITIONS
+                .save(&mut storage, (pool, U64Key::new(id)), &(id * 10))
+                .unwrap();
+        }
+
+        let all = POSITIONS
+            .range(&storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(all.len(), 5);
+        assert_eq!(all[0], (("a".to_string(), 1), 10));
+
+        let v = POSITIONS
+            .prefix_range(
+                &storage,
+                Some(PrefixBound::Exclusive("a")),
+                Some(PrefixBound::Inclusive("c")),
+                Order::Ascending,
+            )
+            .map(|e| e.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(v, vec![("b".to_string(), 1), ("c".to_string(), 5)]);
+
+        let v = POSITIONS
+            .prefix_range(
+                &storage,
+                Some(PrefixBound::Inclusive("a")),
+                Some(PrefixBound::Exclusive("c")),
+                Order::Descending,
+            )
+            .map(|e| e.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            v,
+            vec![
+                ("b".to_string(), 1),
+                ("a".to_string(), 2),
+                ("a".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn children() {
+        let mut storage = MockStorage::new();
+        const POSITIONS: MapCow<U64Key, u64> = MapCow::new_ref("positions");
+
+        POSITIONS.save(&mut storage, 1.into(), &1).unwrap();
+        for pool in ["a", "b", "c"] {
+            let child = POSITIONS.add_child(&mut storage, pool).unwrap();
+            for id in 0..3u64 {
+                child.save(&mut storage, id.into(), &id).unwrap();
+            }
+        }
+        // not recorded, left out of enumeration and clearing
+        POSITIONS
+            .child("d")
+            .save(&mut storage, 1.into(), &1)
+            .unwrap();
+
+        let pool_b = POSITIONS.child("b");
+        assert_eq!(pool_b.load(&storage, 2.into()).unwrap(), 2);
+        assert_eq!(
+            POSITIONS
+                .children(&storage, Some("a"), Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()
+                .unwrap(),
+            vec!["b", "c"]
+        );
+
+        assert!(!POSITIONS.clear_child(&mut storage, "b", 2).unwrap());
+        assert!(POSITIONS.clear_child(&mut storage, "b", 1).unwrap());
+        assert!(!pool_b.has(&storage, 2.into()));
+
+        assert!(!POSITIONS.clear_children(&mut storage, 4).unwrap());
+        assert!(POSITIONS.clear_children(&mut storage, 2).unwrap());
+
+        // empty children count against the limit too
+        for pool in ["e", "f", "g"] {
+            POSITIONS.add_child(&mut storage, pool).unwrap();
+        }
+        assert!(!POSITIONS.clear_children(&mut storage, 2).unwrap());
+        assert_eq!(
+            POSITIONS
+                .children(&storage, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()
+                .unwrap(),
+            vec!["g"]
+        );
+        assert!(POSITIONS.clear_children(&mut storage, 2).unwrap());
+        assert_eq!(
+            POSITIONS
+                .children(&storage, None, Order::Descending)
+                .count(),
+            0
+        );
+
+        // parent and unrecorded children are kept
+        assert_eq!(POSITIONS.load(&storage, 1.into()).unwrap(), 1);
+        assert_eq!(POSITIONS.child("d").load(&storage, 1.into()).unwrap(), 1);
+
+        // a map named with the old plain suffix does not share the registry
+        let suffixed: MapCow<&str, ()> = MapCow::new_ref("positions__children");
+        suffixed.save(&mut storage, "x", &()).unwrap();
+        assert_eq!(POSITIONS.children_namespace(), "9:positions#children");
+        assert_eq!(
+            POSITIONS.children(&storage, None, Order::Ascending).count(),
+            0
+        );
+    }
 }
@@ -0,0 +1,482 @@
+use cosmwasm_std::{to_vec, Order, Pair, StdError, StdResult, Storage};
+use cw_storage_plus::{Bound, IndexList, Prefix};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::helpers::{namespace_upper_bound, namespaces_with_key};
+use super::indexed_map::IndexedMapCow;
+use super::item::ItemCow;
+use super::map::MapCow;
+
+/// Result of one batch of a map migration.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MigrationProgress {
+    /// Number of entries migrated by this batch
+    pub migrated: usize,
+    /// Raw key to continue from in another transaction, `None` once every entry is migrated
+    pub next: Option<Vec<u8>>,
+}
+
+impl MigrationProgress {
+    pub fn is_done(&self) -> bool {
+        self.next.is_none()
+    }
+}
+
+/// Save `old` through `f` into `new`, removing `old` if namespaces differ.
+///
+/// Returns `false` if there is nothing to migrate.
+pub fn migrate_item<Old, New, F>(
+    store: &mut dyn Storage,
+    old: &ItemCow<Old>,
+    new: &ItemCow<New>,
+    f: F,
+) -> StdResult<bool>
+where
+    Old: Serialize + DeserializeOwned,
+    New: Serialize + DeserializeOwned,
+    F: Fn(Old) -> New,
+{
+    let data = match old.may_load(store)? {
+        Some(data) => data,
+        None => return Ok(false),
+    };
+    if old.namespace != new.namespace {
+        old.remove(store);
+    }
+    new.save(store, &f(data))?;
+    Ok(true)
+}
+
+/// Save at most `limit` entries of `old` with raw key after `start_after` through `f` into `new`,
+/// keeping their keys. Entries of `old` are removed if namespaces differ, otherwise they are
+/// replaced in place and the batch must be resumed from `next`.
+pub fn migrate_map<K, Old, New, F>(
+    store: &mut dyn Storage,
+    old: &MapCow<K, Old>,
+    new: &MapCow<K, New>,
+    f: F,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<MigrationProgress>
+where
+    Old: Serialize + DeserializeOwned,
+    New: Serialize + DeserializeOwned,
+    F: Fn(Old) -> New,
+{
+    let old_namespace = old.namespace.as_bytes();
+    let new_namespace = new.namespace.as_bytes();
+    let (entries, progress) = load_page::<Old>(store, old_namespace, start_after, limit)?;
+
+    for (k, data) in entries {
+        if old_namespace != new_namespace {
+            store.remove(&namespaces_with_key(&[old_namespace], &k));
+        }
+        store.set(
+            &namespaces_with_key(&[new_namespace], &k),
+            &to_vec(&f(data))?,
+        );
+    }
+    Ok(progress)
+}
+
+/// Like `migrate_map` for an `IndexedMapCow`, removing index entries of `old` and saving index
+/// entries of `new`. With `f` as identity, this moves a map to another namespace or index set.
+///
+/// Entries are written one after another, a failing batch must be reverted with its transaction.
+pub fn migrate_indexed_map<K, Old, New, I, J, F>(
+    store: &mut dyn Storage,
+    old: &IndexedMapCow<K, Old, I>,
+    new: &IndexedMapCow<K, New, J>,
+    f: F,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<MigrationProgress>
+where
+    Old: Serialize + DeserializeOwned + Clone,
+    New: Serialize + DeserializeOwned + Clone,
+    I: IndexList<Old>,
+    J: IndexList<New>,
+    F: Fn(Old) -> New,
+{
+    let old_namespace = old.pk_namespace.as_bytes();
+    let new_namespace = new.pk_namespace.as_bytes();
+    let (entries, progress) = load_page::<Old>(store, old_namespace, start_after, limit)?;
+
+    for (pk, data) in entries {
+        for index in old.index.get_indexes() {
+            index.remove(store, &pk, &data)?;
+        }
+        store.remove(&namespaces_with_key(&[old_namespace], &pk));
+
+        let data = f(data);
+        for index in new.index.get_indexes() {
+            index.save(store, &pk, &data)?;
+        }
+        store.set(&namespaces_with_key(&[new_namespace], &pk), &to_vec(&data)?);
+    }
+    Ok(progress)
+}
+
+/// Move at most `limit` raw entries of map `from` with raw key after `start_after` to map `to`,
+/// without deserializing them.
+pub fn move_map<K, T>(
+    store: &mut dyn Storage,
+    from: &MapCow<K, T>,
+    to: &MapCow<K, T>,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<MigrationProgress> {
+    move_namespace(
+        store,
+        from.namespace.as_bytes(),
+        to.namespace.as_bytes(),
+        start_after,
+        limit,
+    )
+}
+
+/// Move at most `limit` raw entries stored under namespace `from` with raw key after
+/// `start_after` to namespace `to`, e.g. for a map which type is not at hand anymore.
+///
+/// Nothing is moved when `from` is `to`, and the migration is reported done.
+pub fn move_namespace(
+    store: &mut dyn Storage,
+    from: &[u8],
+    to: &[u8],
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<MigrationProgress> {
+    check_limit(limit)?;
+    if from == to {
+        return Ok(MigrationProgress {
+            migrated: 0,
+            next: None,
+        });
+    }
+
+    let prefix = namespaces_with_key(&[from], b"");
+    let start = match start_after {
+        // first key after `start_after`
+        Some(mut k) => {
+            k.push(0);
+            namespaces_with_key(&[from], &k)
+        }
+        None => prefix.clone(),
+    };
+    let end = namespace_upper_bound(&prefix);
+    let entries = store
+        .range(Some(&start), Some(&end), Order::Ascending)
+        .take(limit.saturating_add(1))
+        .map(|(k, v)| (k[prefix.len()..].to_vec(), v))
+        .collect::<Vec<_>>();
+    let (entries, progress) = into_page(entries, limit);
+
+    for (k, v) in entries {
+        store.remove(&namespaces_with_key(&[from], &k));
+        store.set(&namespaces_with_key(&[to], &k), &v);
+    }
+    Ok(progress)
+}
+
+fn load_page<T: Serialize + DeserializeOwned>(
+    store: &dyn Storage,
+    namespace: &[u8],
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<(Vec<Pair<T>>, MigrationProgress)> {
+    check_limit(limit)?;
+    let entries = Prefix::<T>::new(namespace, &[])
+        .range(
+            store,
+            start_after.map(Bound::Exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit.saturating_add(1))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(into_page(entries, limit))
+}
+
+/// A zero `limit` would report a migration done without migrating anything.
+fn check_limit(limit: usize) -> StdResult<()> {
+    if limit == 0 {
+        return Err(StdError::generic_err(
+            "Migration limit must be greater than zero",
+        ));
+    }
+    Ok(())
+}
+
+fn into_page<T>(mut entries: Vec<Pair<T>>, limit: usize) -> (Vec<Pair<T>>, MigrationProgress) {
+    let has_more = entries.len() > limit;
+    entries.truncate(limit);
+
+    let progress = MigrationProgress {
+        migrated: entries.len(),
+        next: match entries.last() {
+            Some((k, _)) if has_more => Some(k.clone()),
+            _ => None,
+        },
+    };
+    (entries, progress)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::{testing::MockStorage, Addr};
+    use cw_storage_plus::{Index, PrimaryKey, U64Key};
+    use serde::Deserialize;
+
+    use crate::cow::{IndexInspect, IndexListInspect, MultiIndexCow, UniqueIndexCow};
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct ConfigV1 {
+        owner: String,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct ConfigV2 {
+        owner: Addr,
+        paused: bool,
+    }
+
+    fn upgrade(v1: ConfigV1) -> ConfigV2 {
+        ConfigV2 {
+            owner: Addr::unchecked(v1.owner),
+            paused: false,
+        }
+    }
+
+    #[test]
+    fn item() {
+        let mut storage = MockStorage::new();
+        const OLD: ItemCow<ConfigV1> = ItemCow::new_ref("config");
+        const NEW: ItemCow<ConfigV2> = ItemCow::new_ref("config_v2");
+
+        assert!(!migrate_item(&mut storage, &OLD, &NEW, upgrade).unwrap());
+
+        OLD.save(
+            &mut storage,
+            &ConfigV1 {
+                owner: "admin".to_string(),
+            },
+        )
+        .unwrap();
+        assert!(migrate_item(&mut storage, &OLD, &NEW, upgrade).unwrap());
+        assert_eq!(OLD.may_load(&storage).unwrap(), None);
+        assert_eq!(NEW.load(&storage).unwrap().owner, Addr::unchecked("admin"));
+    }
+
+    #[test]
+    fn map_in_place() {
+        let mut storage = MockStorage::new();
+        const OLD: MapCow<U64Key, ConfigV1> = MapCow::new_ref("configs");
+        const NEW: MapCow<U64Key, ConfigV2> = MapCow::new_ref("configs");
+
+        for id in 0..5u64 {
+            let config = ConfigV1 {
+                owner: format!("owner{}", id),
+            };
+            OLD.save(&mut storage, id.into(), &config).unwrap();
+        }
+
+        let mut progress = migrate_map(&mut storage, &OLD, &NEW, upgrade, None, 2).unwrap();
+        assert_eq!(progress.migrated, 2);
+        while !progress.is_done() {
+            progress = migrate_map(&mut storage, &OLD, &NEW, upgrade, progress.next, 2).unwrap();
+        }
+        assert_eq!(progress.migrated, 1);
+
+        assert_eq!(
+            NEW.load(&storage, 4.into()).unwrap().owner,
+            Addr::unchecked("owner4")
+        );
+        assert!(!NEW.load(&storage, 0.into()).unwrap().paused);
+    }
+
+    #[test]
+    fn map_to_namespace() {
+        let mut storage = MockStorage::new();
+        const OLD: MapCow<&str, u64> = MapCow::new_ref("balance");
+        const NEW: MapCow<&str, u64> = MapCow::new_ref("balances");
+        const NEIGHBOUR: MapCow<&str, u64> = MapCow::new_ref("balancf");
+
+        for (k, v) in [("a", 1), ("b", 2), ("c", 3)] {
+            OLD.save(&mut storage, k, &v).unwrap();
+        }
+        NEIGHBOUR.save(&mut storage, "a", &9).unwrap();
+
+        let progress = move_map(&mut storage, &OLD, &NEW, None, 2).unwrap();
+        assert_eq!(progress.next, Some(b"b".to_vec()));
+        // entries are removed from `from`, resuming from scratch works as well
+        let progress = move_map(&mut storage, &OLD, &NEW, None, 2).unwrap();
+        assert_eq!(
+            progress,
+            MigrationProgress {
+                migrated: 1,
+                next: None
+            }
+        );
+
+        assert_eq!(OLD.keys(&storage, None, None, Order::Ascending).count(), 0);
+        assert_eq!(NEW.load(&storage, "c").unwrap(), 3);
+        assert_eq!(NEIGHBOUR.load(&storage, "a").unwrap(), 9);
+
+        // raw resume point
+        let progress = move_namespace(
+            &mut storage,
+            b"balances",
+            b"balance",
+            Some(b"a".to_vec()),
+            5,
+        )
+        .unwrap();
+        assert_eq!(progress.migrated, 2);
+        assert_eq!(OLD.load(&storage, "b").unwrap(), 2);
+        assert_eq!(NEW.load(&storage, "a").unwrap(), 1);
+
+        // moving onto itself is a no-op reported as done
+        let progress = move_map(&mut storage, &NEW, &NEW, None, 1).unwrap();
+        assert!(progress.is_done());
+        assert_eq!(progress.migrated, 0);
+        assert_eq!(NEW.load(&storage, "a").unwrap(), 1);
+    }
+
+    #[test]
+    fn limit_edge_cases() {
+        let mut storage = MockStorage::new();
+        const OLD: MapCow<&str, u64> = MapCow::new_ref("limits");
+        const NEW: MapCow<&str, u64> = MapCow::new_ref("limits_v2");
+
+        for (k, v) in [("a", 1), ("b", 2)] {
+            OLD.save(&mut storage, k, &v).unwrap();
+        }
+
+        // a zero limit would otherwise report done with every entry left behind
+        migrate_map(&mut storage, &OLD, &NEW, |v| v, None, 0).unwrap_err();
+        move_map(&mut storage, &OLD, &NEW, None, 0).unwrap_err();
+        move_map(&mut storage, &OLD, &OLD, None, 0).unwrap_err();
+        assert_eq!(OLD.load(&storage, "a").unwrap(), 1);
+
+        let progress = migrate_map(&mut storage, &OLD, &NEW, |v| v + 1, None, usize::MAX).unwrap();
+        assert_eq!(
+            progress,
+            MigrationProgress {
+                migrated: 2,
+                next: None
+            }
+        );
+        assert_eq!(NEW.load(&storage, "b").unwrap(), 3);
+
+        let progress = move_map(&mut storage, &NEW, &OLD, None, usize::MAX).unwrap();
+        assert_eq!(progress.migrated, 2);
+        assert!(progress.is_done());
+        assert_eq!(OLD.load(&storage, "a").unwrap(), 2);
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Token {
+        owner: Addr,
+        name: String,
+    }
+
+    struct OldIndexes<'a> {
+        owner: MultiIndexCow<'a, (Addr, Vec<u8>), ConfigV1>,
+    }
+
+    impl IndexList<ConfigV1> for OldIndexes<'_> {
+        fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<ConfigV1>> + '_> {
+            let v: Vec<&dyn Index<ConfigV1>> = vec![&self.owner];
+            Box::new(v.into_iter())
+        }
+    }
+
+    struct TokenIndexes<'a> {
+        owner: MultiIndexCow<'a, (Addr, Vec<u8>), Token>,
+        name: UniqueIndexCow<'a, String, Token>,
+    }
+
+    impl IndexList<Token> for TokenIndexes<'_> {
+        fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Token>> + '_> {
+            let v: Vec<&dyn Index<Token>> = vec![&self.owner, &self.name];
+            Box::new(v.into_iter())
+        }
+    }
+
+    impl IndexListInspect<Token> for TokenIndexes<'_> {
+        fn get_inspects(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn IndexInspect<Token>> + '_> {
+            let v: Vec<&dyn IndexInspect<Token>> = vec![&self.owner, &self.name];
+            Box::new(v.into_iter())
+        }
+    }
+
+    #[test]
+    fn indexed_map() {
+        let mut storage = MockStorage::new();
+        let old: IndexedMapCow<U64Key, ConfigV1, OldIndexes> = IndexedMapCow::new_ref(
+            "tokens",
+            OldIndexes {
+                owner: MultiIndexCow::new_ref("tokens", "tokens__owner", |t, k| {
+                    (Addr::unchecked(&t.owner), k)
+                }),
+            },
+        );
+        let new: IndexedMapCow<U64Key, Token, TokenIndexes> = IndexedMapCow::new_ref(
+            "tokens_v2",
+            TokenIndexes {
+                owner: MultiIndexCow::new_ref("tokens_v2", "tokens_v2__owner", |t, k| {
+                    (t.owner.clone(), k)
+                }),
+                name: UniqueIndexCow::new_ref("tokens_v2__name", |t| t.name.clone()),
+            },
+        );
+
+        for (id, owner) in [(1u64, "alice"), (2, "bob"), (3, "carol")] {
+            let token = ConfigV1 {
+                owner: owner.to_string(),
+            };
+            old.save(&mut storage, id.into(), &token).unwrap();
+        }
+
+        let to_token = |t: ConfigV1| Token {
+            name: format!("{}'s", t.owner),
+            owner: Addr::unchecked(t.owner),
+        };
+        let progress = migrate_indexed_map(&mut storage, &old, &new, to_token, None, 2).unwrap();
+        assert_eq!(progress.next, Some(U64Key::new(2).joined_key()));
+
+        assert!(new.check_indexes(&storage).unwrap().is_consistent());
+        assert_eq!(
+            new.index
+                .name
+                .load_by(&storage, "alice's".to_string())
+                .unwrap()
+                .0,
+            U64Key::new(1).joined_key()
+        );
+        assert_eq!(
+            new.index
+                .owner
+                .prefix_de::<U64Key>(Addr::unchecked("bob"))
+                .keys(&storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()
+                .unwrap(),
+            vec![2]
+        );
+        assert_eq!(
+            old.index
+                .owner
+                .prefix_de::<U64Key>(Addr::unchecked("alice"))
+                .keys(&storage, None, None, Order::Ascending)
+                .count(),
+            0
+        );
+
+        let progress =
+            migrate_indexed_map(&mut storage, &old, &new, to_token, progress.next, 2).unwrap();
+        assert!(progress.is_done());
+        assert_eq!(old.keys(&storage, None, None, Order::Ascending).count(), 0);
+        assert!(new.check_indexes(&storage).unwrap().is_consistent());
+    }
+}
@@ -1,18 +1,55 @@
 mod conditional_multi_index;
+mod conditional_unique_index;
 mod custom_dese_index;
+mod deque;
+mod error;
 mod helpers;
+mod index;
 mod indexed_map;
 mod indexed_map_ref;
 mod item;
+mod keys;
 mod map;
+mod migration;
+mod namespace;
+mod ordered_key;
+mod prefix;
+mod registry;
+mod reverse_map;
+mod sequence;
+mod snapshot_item;
+mod snapshot_map;
+mod sorted_set;
+mod transform;
 
 #[cfg(test)]
 mod tests;
 
 pub use conditional_multi_index::ConditionalMultiIndex;
+pub use conditional_unique_index::ConditionalUniqueIndex;
 pub use custom_dese_index::CustomDeseMultiIndex;
+pub use deque::DequeCow;
+pub use error::UniqueViolation;
 pub use helpers::{deserialize_multi_kv_custom_pk, DeserializeFn};
+pub use index::{
+    ConsistencyReport, IndexBuilder, IndexCow, IndexInspect, IndexListInspect, IndexReport,
+    RebuildCursor,
+};
 pub use indexed_map::{IndexedMapCow, MultiIndexCow, UniqueIndexCow};
-pub use indexed_map_ref::IndexedMapRef;
+pub use indexed_map_ref::{ClearProgress, IndexedMapRef};
 pub use item::ItemCow;
+pub use keys::{KeyDeserialize, PrefixBound};
 pub use map::MapCow;
+pub use migration::{
+    migrate_indexed_map, migrate_item, migrate_map, move_map, move_namespace, MigrationProgress,
+};
+pub use namespace::NamespaceBuilder;
+pub use ordered_key::{Asc, Desc};
+pub use prefix::PrefixDe;
+pub use registry::{Namespace, NamespaceConflict, NamespaceRegistry, Namespaced};
+pub use reverse_map::ReverseMapCow;
+pub use sequence::SequenceCow;
+pub use snapshot_item::SnapshotItemCow;
+pub use snapshot_map::SnapshotMapCow;
+pub use sorted_set::{Score, SortedSetCow};
+pub use transform::{deserialize_multi_kv_transformed, Invert, KeyTransform};
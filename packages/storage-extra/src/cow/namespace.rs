@@ -0,0 +1,64 @@
+use super::item::ItemCow;
+use super::map::MapCow;
+
+/// Builds child namespaces from runtime segments, e.g. one map per pool.
+///
+/// The parent is written as `{len}:{parent}` and each segment is appended as `/{len}:{segment}`.
+/// With its length in front the parent or a segment can hold any character, so different
+/// parents and segment lists never build the same namespace: `"pool"` then `"1/1:2"` differs
+/// from `"pool"` then `"1"` then `"2"`, and `"pool/1:a"` alone differs from `"pool"` then `"a"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamespaceBuilder {
+    namespace: String,
+}
+
+impl NamespaceBuilder {
+    pub fn new(parent: &str) -> Self {
+        Self {
+            namespace: format!("{}:{}", parent.len(), parent),
+        }
+    }
+
+    pub fn push(mut self, segment: &str) -> Self {
+        self.namespace
+            .push_str(&format!("/{}:{}", segment.len(), segment));
+        self
+    }
+
+    pub fn build(self) -> String {
+        self.namespace
+    }
+
+    pub fn item<'a, T>(self) -> ItemCow<'a, T> {
+        ItemCow::new_owned(self.build())
+    }
+
+    pub fn map<'a, K, T>(self) -> MapCow<'a, K, T> {
+        MapCow::new_owned(self.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn segments_are_length_prefixed() {
+        assert_eq!(
+            NamespaceBuilder::new("pool").push("12").push("a").build(),
+            "4:pool/2:12/1:a"
+        );
+
+        let nested = NamespaceBuilder::new("pool").push("1").push("2").build();
+        let single = NamespaceBuilder::new("pool").push("1/1:2").build();
+        assert_ne!(nested, single);
+        assert_ne!(
+            NamespaceBuilder::new("pool").push("").build(),
+            NamespaceBuilder::new("pool").build()
+        );
+        assert_ne!(
+            NamespaceBuilder::new("pool/1:a").build(),
+            NamespaceBuilder::new("pool").push("a").build()
+        );
+    }
+}
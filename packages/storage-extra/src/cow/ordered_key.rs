@@ -0,0 +1,226 @@
+use cosmwasm_std::StdResult;
+use cw_storage_plus::{Prefixer, PrimaryKey};
+use std::marker::PhantomData;
+
+use super::keys::KeyDeserialize;
+use super::transform::{Invert, KeyTransform};
+
+/// Key component iterated in ascending order, stored as `K` is.
+///
+/// Together with `Desc`, components of a tuple key declare their own order, e.g.
+/// `(Asc<&str>, Desc<U128Key>, Asc<U64Key>)` for market ascending, price descending and time
+/// ascending.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Asc<K> {
+    key: Vec<u8>,
+    key_type: PhantomData<K>,
+}
+
+/// Key component iterated in descending order, stored as `K` with every byte inverted.
+///
+/// Order is exactly reversed for fixed width keys, like integer keys or addresses of the same
+/// length. A shorter key still sorts before every key it prefixes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Desc<K> {
+    key: Vec<u8>,
+    key_type: PhantomData<K>,
+}
+
+impl<K> Asc<K> {
+    pub fn new<'k>(key: K) -> Self
+    where
+        K: PrimaryKey<'k>,
+    {
+        Self {
+            key: key.joined_key(),
+            key_type: PhantomData,
+        }
+    }
+}
+
+impl<K> Desc<K> {
+    pub fn new<'k>(key: K) -> Self
+    where
+        K: PrimaryKey<'k>,
+    {
+        Self {
+            key: Invert::forward(key.joined_key()),
+            key_type: PhantomData,
+        }
+    }
+}
+
+impl<'k, K: PrimaryKey<'k>> From<K> for Asc<K> {
+    fn from(key: K) -> Self {
+        Self::new(key)
+    }
+}
+
+impl<'k, K: PrimaryKey<'k>> From<K> for Desc<K> {
+    fn from(key: K) -> Self {
+        Self::new(key)
+    }
+}
+
+impl<'a, K: Clone> PrimaryKey<'a> for Asc<K> {
+    type Prefix = ();
+    type SubPrefix = ();
+
+    fn key(&self) -> Vec<&[u8]> {
+        vec![&self.key]
+    }
+}
+
+impl<'a, K> Prefixer<'a> for Asc<K> {
+    fn prefix(&self) -> Vec<&[u8]> {
+        vec![&self.key]
+    }
+}
+
+impl<K: KeyDeserialize> KeyDeserialize for Asc<K> {
+    type Output = K::Output;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+        K::from_vec(value)
+    }
+}
+
+impl<'a, K: Clone> PrimaryKey<'a> for Desc<K> {
+    type Prefix = ();
+    type SubPrefix = ();
+
+    fn key(&self) -> Vec<&[u8]> {
+        vec![&self.key]
+    }
+}
+
+impl<'a, K> Prefixer<'a> for Desc<K> {
+    fn prefix(&self) -> Vec<&[u8]> {
+        vec![&self.key]
+    }
+}
+
+impl<K: KeyDeserialize> KeyDeserialize for Desc<K> {
+    type Output = K::Output;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self::Output> {
+        K::from_vec(Invert::inverse(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::{testing::MockStorage, Order};
+    use cw_storage_plus::{Index, IndexList, U128Key, U64Key};
+    use serde::{Deserialize, Serialize};
+
+    use crate::cow::{IndexedMapCow, MapCow, MultiIndexCow};
+
+    type BookKey<'a> = (Asc<&'a str>, Desc<U128Key>, Asc<U64Key>);
+
+    fn book_key(market: &str, price: u128, time: u64) -> BookKey<'_> {
+        (
+            Asc::new(market),
+            Desc::new(U128Key::new(price)),
+            Asc::new(U64Key::new(time)),
+        )
+    }
+
+    #[test]
+    fn mixed_order_map() {
+        let mut storage = MockStorage::new();
+        const BOOK: MapCow<BookKey, u64> = MapCow::new_ref("book");
+
+        for (i, (market, price, time)) in [
+            ("eth", 10, 1),
+            ("btc", 5, 2),
+            ("btc", 7, 3),
+            ("btc", 7, 1),
+            ("btc", 5, 0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            BOOK.save(&mut storage, book_key(market, price, time), &(i as u64))
+                .unwrap();
+        }
+
+        // market ascending, price descending, time ascending
+        assert_eq!(
+            BOOK.keys(&storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()
+                .unwrap(),
+            vec![
+                ("btc".to_string(), 7, 1),
+                ("btc".to_string(), 7, 3),
+                ("btc".to_string(), 5, 0),
+                ("btc".to_string(), 5, 2),
+                ("eth".to_string(), 10, 1),
+            ]
+        );
+
+        assert_eq!(
+            BOOK.prefix((Asc::new("btc"), Desc::new(U128Key::new(5))))
+                .range(&storage, None, None, Order::Ascending)
+                .map(|e| e.unwrap().1)
+                .collect::<Vec<_>>(),
+            vec![4, 1]
+        );
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Bid {
+        market: String,
+        price: u64,
+    }
+
+    type PriceKey = (Asc<String>, Desc<U64Key>, Vec<u8>);
+
+    struct BidIndexes<'a> {
+        price: MultiIndexCow<'a, PriceKey, Bid>,
+    }
+
+    impl IndexList<Bid> for BidIndexes<'_> {
+        fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Bid>> + '_> {
+            let v: Vec<&dyn Index<Bid>> = vec![&self.price];
+            Box::new(v.into_iter())
+        }
+    }
+
+    #[test]
+    fn mixed_order_index() {
+        let mut storage = MockStorage::new();
+        const BIDS: IndexedMapCow<U64Key, Bid, BidIndexes> = IndexedMapCow::new_ref(
+            "bids",
+            BidIndexes {
+                price: MultiIndexCow::new_ref("bids", "bids__price", |b, k| {
+                    (
+                        Asc::new(b.market.clone()),
+                        Desc::new(U64Key::new(b.price)),
+                        k,
+                    )
+                }),
+            },
+        );
+
+        for (id, price) in [(0u64, 3u64), (1, 9), (2, 3), (3, 6)] {
+            let bid = Bid {
+                market: "btc".to_string(),
+                price,
+            };
+            BIDS.save(&mut storage, id.into(), &bid).unwrap();
+        }
+
+        // highest price first, then by id
+        assert_eq!(
+            BIDS.index
+                .price
+                .sub_prefix_de::<U64Key>(Asc::new("btc".to_string()))
+                .keys(&storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()
+                .unwrap(),
+            vec![1, 3, 0, 2]
+        );
+    }
+}
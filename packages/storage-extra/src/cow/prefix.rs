@@ -0,0 +1,88 @@
+use cosmwasm_std::{Order, Pair, StdResult, Storage};
+use cw_storage_plus::{range_with_prefix, Bound, Prefix};
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
+
+use super::helpers::{deserialize_key_kv, namespaces_with_key};
+use super::index::IndexInspect;
+use super::keys::KeyDeserialize;
+
+/// `Prefix` of an index, yielding primary keys deserialized through `PK` instead of raw bytes.
+pub struct PrefixDe<'k, PK, T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    prefix: Prefix<T>,
+    // raw prefix of index entries, and the index reading their pk
+    storage_prefix: Vec<u8>,
+    index: &'k dyn IndexInspect<T>,
+    pk_type: PhantomData<PK>,
+}
+
+impl<'k, PK, T> PrefixDe<'k, PK, T>
+where
+    PK: KeyDeserialize,
+    T: Serialize + DeserializeOwned,
+{
+    /// `prefix` of `index` built from `sub_names` under its index namespace.
+    pub fn new(prefix: Prefix<T>, index: &'k dyn IndexInspect<T>, sub_names: &[&[u8]]) -> Self {
+        let mut names = vec![index.namespace().as_bytes()];
+        names.extend_from_slice(sub_names);
+        Self {
+            prefix,
+            storage_prefix: namespaces_with_key(&names, b""),
+            index,
+            pk_type: PhantomData,
+        }
+    }
+
+    /// Same as `Prefix::range`, with raw primary keys.
+    pub fn range_raw<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<Pair<T>>> + 'c>
+    where
+        T: 'c,
+    {
+        self.prefix.range(store, min, max, order)
+    }
+
+    pub fn range<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<(PK::Output, T)>> + 'c>
+    where
+        T: 'c,
+        PK::Output: 'c,
+    {
+        let de_fn: fn(_) -> _ = deserialize_key_kv::<PK, T>;
+        Box::new(self.range_raw(store, min, max, order).map(de_fn))
+    }
+
+    /// Primary keys only, read from the index entries without loading data of the primary map.
+    ///
+    /// Keys are the ones stored by the index, a custom `dese_fn` rewriting them is not applied.
+    pub fn keys<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<PK::Output>> + 'c>
+    where
+        'k: 'c,
+        PK::Output: 'c,
+    {
+        let index = self.index;
+        Box::new(
+            range_with_prefix(store, &self.storage_prefix, min, max, order)
+                .map(move |(k, v)| PK::from_vec(index.pk_of(&k, &v)?)),
+        )
+    }
+}
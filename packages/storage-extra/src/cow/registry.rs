@@ -0,0 +1,329 @@
+use cosmwasm_std::{StdError, StdResult};
+use cw_storage_plus::PrimaryKey;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::conditional_multi_index::ConditionalMultiIndex;
+use super::conditional_unique_index::ConditionalUniqueIndex;
+use super::custom_dese_index::CustomDeseMultiIndex;
+use super::deque::DequeCow;
+use super::helpers::{namespaces_with_key, pruned_namespace};
+use super::index::{IndexCow, IndexListInspect};
+use super::indexed_map::{IndexedMapCow, MultiIndexCow, UniqueIndexCow};
+use super::item::ItemCow;
+use super::map::MapCow;
+use super::reverse_map::ReverseMapCow;
+use super::sequence::SequenceCow;
+use super::snapshot_item::SnapshotItemCow;
+use super::snapshot_map::SnapshotMapCow;
+use super::sorted_set::SortedSetCow;
+
+/// Raw storage area declared by a Cow type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Namespace {
+    pub name: String,
+    /// Raw key, or raw prefix of every key if not `exact`
+    pub raw: Vec<u8>,
+    /// Single key stored as is, like `ItemCow`
+    pub exact: bool,
+}
+
+impl Namespace {
+    /// Single key stored under raw `name`, like `ItemCow`.
+    pub fn exact(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            raw: name.as_bytes().to_vec(),
+            exact: true,
+        }
+    }
+
+    /// Keys stored under length-prefixed `name`, like `MapCow`.
+    pub fn prefixed(name: &str) -> Self {
+        Self::nested(&[name])
+    }
+
+    /// Keys stored under several length-prefixed namespaces, like a sub prefix of a map.
+    pub fn nested(names: &[&str]) -> Self {
+        let names_bytes = names.iter().map(|n| n.as_bytes()).collect::<Vec<_>>();
+        Self {
+            name: names.join("/"),
+            raw: namespaces_with_key(&names_bytes, b""),
+            exact: false,
+        }
+    }
+
+    /// Whether some key can be stored under both namespaces.
+    pub fn overlaps(&self, other: &Namespace) -> bool {
+        self.raw == other.raw
+            || (!other.exact && self.raw.starts_with(&other.raw))
+            || (!self.exact && other.raw.starts_with(&self.raw))
+    }
+}
+
+/// Every namespace a Cow type stores under.
+pub trait Namespaced {
+    fn namespaces(&self) -> Vec<Namespace>;
+}
+
+/// Two declared namespaces sharing some storage key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamespaceConflict {
+    /// Label given on registration, and namespace
+    pub first: (String, Namespace),
+    pub second: (String, Namespace),
+    /// Same raw namespace, otherwise one namespace prefixes keys of the other
+    pub duplicate: bool,
+}
+
+/// Collects namespaces of Cow types to report duplicates and prefix overlaps, meant for tests
+/// and instantiation checks of contracts building namespaces at runtime.
+///
+/// Primary namespace shared by an index and its map is expected, only index namespaces of
+/// index types are collected.
+#[derive(Clone, Debug, Default)]
+pub struct NamespaceRegistry {
+    namespaces: Vec<(String, Namespace)>,
+}
+
+impl NamespaceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register every namespace of `storage` under `label`.
+    pub fn register<N: Namespaced + ?Sized>(&mut self, label: &str, storage: &N) -> &mut Self {
+        for namespace in storage.namespaces() {
+            self.register_namespace(label, namespace);
+        }
+        self
+    }
+
+    pub fn register_namespace(&mut self, label: &str, namespace: Namespace) -> &mut Self {
+        self.namespaces.push((label.to_string(), namespace));
+        self
+    }
+
+    pub fn conflicts(&self) -> Vec<NamespaceConflict> {
+        let mut conflicts = vec![];
+        for (i, first) in self.namespaces.iter().enumerate() {
+            for second in &self.namespaces[i + 1..] {
+                if first.1.overlaps(&second.1) {
+                    conflicts.push(NamespaceConflict {
+                        first: first.clone(),
+                        second: second.clone(),
+                        duplicate: first.1.raw == second.1.raw,
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Fail on the first conflict found.
+    pub fn check(&self) -> StdResult<()> {
+        match self.conflicts().first() {
+            None => Ok(()),
+            Some(c) => Err(StdError::generic_err(format!(
+                "Namespace {} of {} {} namespace {} of {}",
+                c.first.1.name,
+                c.first.0,
+                if c.duplicate {
+                    "duplicates"
+                } else {
+                    "overlaps"
+                },
+                c.second.1.name,
+                c.second.0,
+            ))),
+        }
+    }
+}
+
+impl<T> Namespaced for ItemCow<'_, T> {
+    fn namespaces(&self) -> Vec<Namespace> {
+        vec![Namespace::exact(&self.namespace)]
+    }
+}
+
+impl<K, T> Namespaced for MapCow<'_, K, T> {
+    fn namespaces(&self) -> Vec<Namespace> {
+        vec![
+            Namespace::prefixed(&self.namespace),
+            Namespace::prefixed(&self.children_namespace()),
+        ]
+    }
+}
+
+impl<'a, K, T, I> Namespaced for IndexedMapCow<'a, K, T, I>
+where
+    I: IndexListInspect<T>,
+{
+    fn namespaces(&self) -> Vec<Namespace> {
+        let mut namespaces = vec![Namespace::prefixed(&self.pk_namespace)];
+        namespaces.extend(
+            self.index
+                .get_inspects()
+                .map(|index| Namespace::prefixed(index.namespace())),
+        );
+        namespaces
+    }
+}
+
+macro_rules! impl_index_namespaced {
+    ($($index:ident),* $(,)?) => {
+        $(
+            impl<K, T> Namespaced for $index<'_, K, T>
+            where
+                T: Serialize + DeserializeOwned + Clone,
+                K: for<'k> PrimaryKey<'k>,
+            {
+                fn namespaces(&self) -> Vec<Namespace> {
+                    vec![Namespace::prefixed(self.idx_namespace())]
+                }
+            }
+        )*
+    };
+}
+
+impl_index_namespaced![
+    MultiIndexCow,
+    UniqueIndexCow,
+    CustomDeseMultiIndex,
+    ConditionalMultiIndex,
+    ConditionalUniqueIndex,
+];
+
+impl<K, T> Namespaced for SnapshotMapCow<'_, K, T> {
+    fn namespaces(&self) -> Vec<Namespace> {
+        vec![
+            Namespace::prefixed(&self.namespace),
+            Namespace::prefixed(&self.checkpoints),
+            Namespace::prefixed(&self.changelog),
+            Namespace::prefixed(&pruned_namespace(&self.changelog)),
+        ]
+    }
+}
+
+impl<T> Namespaced for SnapshotItemCow<'_, T> {
+    fn namespaces(&self) -> Vec<Namespace> {
+        vec![
+            Namespace::exact(&self.namespace),
+            Namespace::prefixed(&self.checkpoints),
+            Namespace::prefixed(&self.changelog),
+            Namespace::prefixed(&pruned_namespace(&self.changelog)),
+        ]
+    }
+}
+
+impl<T> Namespaced for DequeCow<'_, T> {
+    fn namespaces(&self) -> Vec<Namespace> {
+        vec![Namespace::prefixed(&self.namespace)]
+    }
+}
+
+impl Namespaced for SequenceCow<'_> {
+    fn namespaces(&self) -> Vec<Namespace> {
+        self.item.namespaces()
+    }
+}
+
+impl<M, S> Namespaced for SortedSetCow<'_, M, S> {
+    fn namespaces(&self) -> Vec<Namespace> {
+        vec![
+            Namespace::prefixed(&self.member_namespace),
+            Namespace::prefixed(&self.score_namespace),
+        ]
+    }
+}
+
+impl<K, V> Namespaced for ReverseMapCow<'_, K, V> {
+    fn namespaces(&self) -> Vec<Namespace> {
+        // inner map is private, so it never records children
+        vec![
+            Namespace::prefixed(&self.map.namespace),
+            Namespace::prefixed(&self.reverse_namespace),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::Addr;
+    use cw_storage_plus::U64Key;
+
+    #[test]
+    fn overlaps() {
+        // length prefix keeps maps with a common name prefix apart
+        assert!(!Namespace::prefixed("pool").overlaps(&Namespace::prefixed("pool__idx")));
+        assert!(Namespace::prefixed("pool").overlaps(&Namespace::prefixed("pool")));
+
+        // single keys only collide when equal
+        assert!(!Namespace::exact("config").overlaps(&Namespace::exact("config2")));
+        assert!(Namespace::exact("config").overlaps(&Namespace::exact("config")));
+
+        // a single key falling under a map
+        let map = Namespace::prefixed("a");
+        let item = Namespace::exact("\0\x01a\0");
+        assert!(item.overlaps(&map));
+        assert!(map.overlaps(&item));
+
+        // a sub prefix is part of its parent map
+        assert!(Namespace::nested(&["pool", "1"]).overlaps(&Namespace::prefixed("pool")));
+        assert!(!Namespace::nested(&["pool", "1"]).overlaps(&Namespace::nested(&["pool", "2"])));
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Clone)]
+    struct Data {
+        owner: Addr,
+    }
+
+    struct DataIndexes<'a> {
+        owner: MultiIndexCow<'a, (Addr, Vec<u8>), Data>,
+    }
+
+    impl IndexListInspect<Data> for DataIndexes<'_> {
+        fn get_inspects(
+            &'_ self,
+        ) -> Box<dyn Iterator<Item = &'_ dyn super::super::IndexInspect<Data>> + '_> {
+            let v: Vec<&dyn super::super::IndexInspect<Data>> = vec![&self.owner];
+            Box::new(v.into_iter())
+        }
+    }
+
+    #[test]
+    fn registry() {
+        let pool_id = 1;
+        let config: ItemCow<u64> = ItemCow::new_owned(format!("pool_{}", pool_id));
+        let positions: MapCow<U64Key, u64> = MapCow::new_owned(format!("pool_{}", pool_id));
+        let data: IndexedMapCow<U64Key, Data, DataIndexes> = IndexedMapCow::new_ref(
+            "data",
+            DataIndexes {
+                owner: MultiIndexCow::new_ref("data", "data__owner", |d, k| (d.owner.clone(), k)),
+            },
+        );
+        let queue: DequeCow<u64> = DequeCow::new_ref("data__owner");
+
+        let mut registry = NamespaceRegistry::new();
+        registry
+            .register("config", &config)
+            .register("positions", &positions)
+            .register("data", &data);
+        registry.check().unwrap();
+        assert_eq!(
+            positions.namespaces()[1],
+            Namespace::prefixed("6:pool_1#children")
+        );
+
+        registry.register("queue", &queue);
+        let conflicts = registry.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].first.0, "data");
+        assert_eq!(conflicts[0].second.0, "queue");
+        assert!(conflicts[0].duplicate);
+        assert_eq!(
+            registry.check().unwrap_err().to_string(),
+            "Generic error: Namespace data__owner of data duplicates namespace data__owner of queue"
+        );
+    }
+}
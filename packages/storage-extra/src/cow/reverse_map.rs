@@ -0,0 +1,256 @@
+use cosmwasm_std::{from_slice, to_vec, Binary, Order, StdError, StdResult, Storage};
+use cw_storage_plus::{Bound, Prefix, PrimaryKey};
+use serde::{de::DeserializeOwned, Serialize};
+use std::borrow::Cow;
+
+use super::error::UniqueViolation;
+use super::helpers::namespaces_with_key;
+use super::keys::KeyDeserialize;
+use super::map::MapCow;
+
+/// `MapCow` keeping a one to one reverse lookup from value to key under `reverse_namespace`.
+///
+/// Primary entries keep the layout of `MapCow`, so an existing map can be wrapped as is and its
+/// reverse lookup backfilled with `rebuild_reverse`.
+#[derive(Debug, Clone)]
+pub struct ReverseMapCow<'a, K, V> {
+    pub(crate) map: MapCow<'a, K, V>,
+    pub(crate) reverse_namespace: Cow<'a, str>,
+}
+
+impl<'a, 'k, K, V> ReverseMapCow<'a, K, V>
+where
+    'k: 'a,
+{
+    pub const fn new_owned(namespace: String, reverse_namespace: String) -> Self {
+        Self {
+            map: MapCow::new_owned(namespace),
+            reverse_namespace: Cow::Owned(reverse_namespace),
+        }
+    }
+
+    pub const fn new_ref(namespace: &'k str, reverse_namespace: &'k str) -> Self {
+        Self {
+            map: MapCow::new_ref(namespace),
+            reverse_namespace: Cow::Borrowed(reverse_namespace),
+        }
+    }
+}
+
+impl<'a, 'key, K, V> ReverseMapCow<'a, K, V>
+where
+    K: PrimaryKey<'key>,
+    V: Serialize + DeserializeOwned + for<'v> PrimaryKey<'v>,
+    'key: 'a,
+{
+    /// Primary map, writing through it leaves the reverse lookup behind.
+    pub fn map(&self) -> &MapCow<'a, K, V> {
+        &self.map
+    }
+
+    fn reverse_key(&self, value: &V) -> Vec<u8> {
+        namespaces_with_key(&[self.reverse_namespace.as_bytes()], &value.joined_key())
+    }
+
+    fn load_owner(&self, store: &dyn Storage, value: &V) -> StdResult<Option<Binary>> {
+        store
+            .get(&self.reverse_key(value))
+            .map(|v| from_slice(&v))
+            .transpose()
+    }
+
+    fn save_reverse(&self, store: &mut dyn Storage, pk: &[u8], value: &V) -> StdResult<()> {
+        // every value maps back to a single key
+        if let Some(owner) = self.load_owner(store, value)? {
+            if owner.as_slice() != pk {
+                return Err(UniqueViolation {
+                    index: self.reverse_namespace.to_string(),
+                    key: value.joined_key().into(),
+                }
+                .into());
+            }
+        }
+        store.set(&self.reverse_key(value), &to_vec(&Binary::from(pk))?);
+        Ok(())
+    }
+
+    fn remove_reverse(&self, store: &mut dyn Storage, pk: &[u8], value: &V) -> StdResult<()> {
+        if let Some(owner) = self.load_owner(store, value)? {
+            if owner.as_slice() == pk {
+                store.remove(&self.reverse_key(value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Save `value` under `k`, fails with `UniqueViolation` if `value` is kept by another key.
+    pub fn save(&'key self, store: &mut dyn Storage, k: K, value: &V) -> StdResult<()> {
+        let pk = k.joined_key();
+        self.save_reverse(store, &pk, value)?;
+
+        if let Some(old) = self.map.may_load(store, k.clone())? {
+            if old.joined_key() != value.joined_key() {
+                self.remove_reverse(store, &pk, &old)?;
+            }
+        }
+        self.map.save(store, k, value)
+    }
+
+    pub fn remove(&'key self, store: &mut dyn Storage, k: K) -> StdResult<()> {
+        if let Some(old) = self.map.may_load(store, k.clone())? {
+            self.remove_reverse(store, &k.joined_key(), &old)?;
+        }
+        self.map.remove(store, k);
+        Ok(())
+    }
+
+    pub fn update<A, E>(&'key self, store: &mut dyn Storage, k: K, action: A) -> Result<V, E>
+    where
+        A: FnOnce(Option<V>) -> Result<V, E>,
+        E: From<StdError>,
+    {
+        let output = action(self.map.may_load(store, k.clone())?)?;
+        self.save(store, k, &output)?;
+        Ok(output)
+    }
+
+    pub fn load(&'key self, store: &dyn Storage, k: K) -> StdResult<V> {
+        self.map.load(store, k)
+    }
+
+    pub fn may_load(&'key self, store: &dyn Storage, k: K) -> StdResult<Option<V>> {
+        self.map.may_load(store, k)
+    }
+
+    /// Key keeping `value`.
+    pub fn load_key(&self, store: &dyn Storage, value: &V) -> StdResult<K::Output>
+    where
+        K: KeyDeserialize,
+    {
+        self.may_load_key(store, value)?
+            .ok_or_else(|| StdError::not_found("reverse key"))
+    }
+
+    /// Key keeping `value`, if any.
+    pub fn may_load_key(&self, store: &dyn Storage, value: &V) -> StdResult<Option<K::Output>>
+    where
+        K: KeyDeserialize,
+    {
+        self.load_owner(store, value)?
+            .map(|pk| K::from_vec(pk.into()))
+            .transpose()
+    }
+
+    /// Backfill reverse lookup of an existing map, at most `limit` entries per call.
+    ///
+    /// Returns raw key to continue from in another transaction, `None` once every entry has been
+    /// processed.
+    pub fn rebuild_reverse(
+        &self,
+        store: &mut dyn Storage,
+        start_after: Option<Vec<u8>>,
+        limit: usize,
+    ) -> StdResult<Option<Vec<u8>>> {
+        let mut entries = Prefix::<V>::new(self.map.namespace.as_bytes(), &[])
+            .range(
+                store,
+                start_after.map(Bound::Exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit + 1)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let has_more = entries.len() > limit;
+        entries.truncate(limit);
+
+        for (pk, value) in &entries {
+            self.save_reverse(store, pk, value)?;
+        }
+
+        Ok(match entries.pop() {
+            Some((pk, _)) if has_more => Some(pk),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::{testing::MockStorage, Addr};
+    use cw_storage_plus::U64Key;
+
+    const TOKENS: ReverseMapCow<U64Key, String> = ReverseMapCow::new_ref("token", "token__denom");
+
+    #[test]
+    fn save_and_reverse() {
+        let mut storage = MockStorage::new();
+
+        TOKENS
+            .save(&mut storage, 1.into(), &"uluna".to_string())
+            .unwrap();
+        TOKENS
+            .save(&mut storage, 2.into(), &"uusd".to_string())
+            .unwrap();
+        assert_eq!(TOKENS.load_key(&storage, &"uusd".to_string()).unwrap(), 2);
+
+        // value kept by another key
+        let err = TOKENS
+            .save(&mut storage, 3.into(), &"uusd".to_string())
+            .unwrap_err();
+        assert!(UniqueViolation::from_std_error(&err).is_some());
+        assert_eq!(TOKENS.may_load(&storage, 3.into()).unwrap(), None);
+
+        // moving a key to another value releases the old one
+        TOKENS
+            .save(&mut storage, 2.into(), &"ukrw".to_string())
+            .unwrap();
+        assert_eq!(
+            TOKENS.may_load_key(&storage, &"uusd".to_string()).unwrap(),
+            None
+        );
+        TOKENS
+            .save(&mut storage, 3.into(), &"uusd".to_string())
+            .unwrap();
+
+        // re-saving the same value is fine
+        TOKENS
+            .update(&mut storage, 1.into(), |v| -> StdResult<_> {
+                Ok(v.unwrap())
+            })
+            .unwrap();
+        assert_eq!(TOKENS.load_key(&storage, &"uluna".to_string()).unwrap(), 1);
+
+        TOKENS.remove(&mut storage, 1.into()).unwrap();
+        TOKENS.load_key(&storage, &"uluna".to_string()).unwrap_err();
+        assert_eq!(TOKENS.may_load(&storage, 1.into()).unwrap(), None);
+    }
+
+    #[test]
+    fn wrap_existing_map() {
+        let mut storage = MockStorage::new();
+        let owners: MapCow<&str, Addr> = MapCow::new_owned("nft_owner".to_string());
+        for (token, owner) in [("a", "x"), ("b", "y"), ("c", "z")] {
+            owners
+                .save(&mut storage, token, &Addr::unchecked(owner))
+                .unwrap();
+        }
+
+        let reverse: ReverseMapCow<&str, Addr> =
+            ReverseMapCow::new_owned("nft_owner".to_string(), "nft_owner__reverse".to_string());
+        assert_eq!(reverse.load(&storage, "b").unwrap(), Addr::unchecked("y"));
+
+        let cursor = reverse.rebuild_reverse(&mut storage, None, 2).unwrap();
+        assert_eq!(cursor, Some(b"b".to_vec()));
+        assert_eq!(
+            reverse.rebuild_reverse(&mut storage, cursor, 2).unwrap(),
+            None
+        );
+
+        assert_eq!(
+            reverse.load_key(&storage, &Addr::unchecked("z")).unwrap(),
+            "c"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use cosmwasm_std::{StdError, StdResult, Storage};
+
+use super::item::ItemCow;
+
+/// Monotonically increasing `u64` counter, e.g. to allocate primary keys.
+#[derive(Debug, Clone)]
+pub struct SequenceCow<'a> {
+    pub(crate) item: ItemCow<'a, u64>,
+}
+
+impl<'a, 'k> SequenceCow<'a>
+where
+    'k: 'a,
+{
+    pub const fn new_owned(namespace: String) -> Self {
+        Self {
+            item: ItemCow::new_owned(namespace),
+        }
+    }
+
+    pub const fn new_ref(namespace: &'k str) -> Self {
+        Self {
+            item: ItemCow::new_ref(namespace),
+        }
+    }
+}
+
+impl<'a> SequenceCow<'a> {
+    /// Last allocated value, `0` if nothing is allocated yet.
+    pub fn current(&self, store: &dyn Storage) -> StdResult<u64> {
+        Ok(self.item.may_load(store)?.unwrap_or_default())
+    }
+
+    /// Allocate and return the next value, starting from `1`.
+    pub fn next(&self, store: &mut dyn Storage) -> StdResult<u64> {
+        let next = self.peek_next(store)?;
+        self.item.save(store, &next)?;
+        Ok(next)
+    }
+
+    /// Value to be returned by `next`, without allocating it.
+    pub(crate) fn peek_next(&self, store: &dyn Storage) -> StdResult<u64> {
+        self.current(store)?
+            .checked_add(1)
+            .ok_or_else(|| StdError::generic_err("Sequence overflow"))
+    }
+
+    pub(crate) fn set(&self, store: &mut dyn Storage, value: u64) -> StdResult<()> {
+        self.item.save(store, &value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+
+    #[test]
+    fn next_and_current() {
+        let mut storage = MockStorage::new();
+        const SEQ: SequenceCow = SequenceCow::new_ref("seq");
+        let other = SequenceCow::new_owned("other_seq".to_string());
+
+        assert_eq!(SEQ.current(&storage).unwrap(), 0);
+        assert_eq!(SEQ.next(&mut storage).unwrap(), 1);
+        assert_eq!(SEQ.next(&mut storage).unwrap(), 2);
+        assert_eq!(SEQ.current(&storage).unwrap(), 2);
+
+        assert_eq!(other.next(&mut storage).unwrap(), 1);
+    }
+
+    #[test]
+    fn overflow() {
+        let mut storage = MockStorage::new();
+        const SEQ: SequenceCow = SequenceCow::new_ref("seq");
+
+        SEQ.set(&mut storage, u64::MAX).unwrap();
+        SEQ.next(&mut storage).unwrap_err();
+        assert_eq!(SEQ.current(&storage).unwrap(), u64::MAX);
+    }
+}
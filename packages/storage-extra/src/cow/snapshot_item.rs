@@ -0,0 +1,174 @@
+use cosmwasm_std::{StdError, StdResult, Storage};
+use cw_storage_plus::{SnapshotItem, Strategy};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{borrow::Cow, marker::PhantomData};
+
+use super::helpers::{assert_not_pruned, prune_changelog};
+
+/// Like `SnapshotItem` from `cw-storage-plus` but in `Cow`.
+#[derive(Debug, Clone)]
+pub struct SnapshotItemCow<'a, T> {
+    pub(crate) namespace: Cow<'a, str>,
+    pub(crate) checkpoints: Cow<'a, str>,
+    pub(crate) changelog: Cow<'a, str>,
+    strategy: Strategy,
+    data_type: PhantomData<T>,
+}
+
+impl<'a, 'k, T> SnapshotItemCow<'a, T>
+where
+    'k: 'a,
+{
+    pub const fn new_owned(
+        namespace: String,
+        checkpoints: String,
+        changelog: String,
+        strategy: Strategy,
+    ) -> Self {
+        Self {
+            namespace: Cow::Owned(namespace),
+            checkpoints: Cow::Owned(checkpoints),
+            changelog: Cow::Owned(changelog),
+            strategy,
+            data_type: PhantomData,
+        }
+    }
+
+    pub const fn new_ref(
+        namespace: &'k str,
+        checkpoints: &'k str,
+        changelog: &'k str,
+        strategy: Strategy,
+    ) -> Self {
+        Self {
+            namespace: Cow::Borrowed(namespace),
+            checkpoints: Cow::Borrowed(checkpoints),
+            changelog: Cow::Borrowed(changelog),
+            strategy,
+            data_type: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> SnapshotItemCow<'a, T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+{
+    pub fn snapshot_item(&self) -> SnapshotItem<'_, T> {
+        SnapshotItem::new(
+            &self.namespace,
+            &self.checkpoints,
+            &self.changelog,
+            self.strategy,
+        )
+    }
+
+    pub fn add_checkpoint(&self, store: &mut dyn Storage, height: u64) -> StdResult<()> {
+        self.snapshot_item().add_checkpoint(store, height)
+    }
+
+    pub fn remove_checkpoint(&self, store: &mut dyn Storage, height: u64) -> StdResult<()> {
+        self.snapshot_item().remove_checkpoint(store, height)
+    }
+
+    pub fn assert_checkpointed(&self, store: &dyn Storage, height: u64) -> StdResult<()> {
+        self.snapshot_item().assert_checkpointed(store, height)
+    }
+
+    pub fn save(&self, store: &mut dyn Storage, data: &T, height: u64) -> StdResult<()> {
+        self.snapshot_item().save(store, data, height)
+    }
+
+    pub fn remove(&self, store: &mut dyn Storage, height: u64) -> StdResult<()> {
+        self.snapshot_item().remove(store, height)
+    }
+
+    pub fn load(&self, store: &dyn Storage) -> StdResult<T> {
+        self.snapshot_item().load(store)
+    }
+
+    pub fn may_load(&self, store: &dyn Storage) -> StdResult<Option<T>> {
+        self.snapshot_item().may_load(store)
+    }
+
+    /// Value at the start of block `height`. Any height is available with
+    /// `Strategy::EveryBlock`, only checkpointed ones with `Strategy::Selected` and none with
+    /// `Strategy::Never`. Fails below the height the item was pruned to.
+    pub fn may_load_at_height(&self, store: &dyn Storage, height: u64) -> StdResult<Option<T>> {
+        assert_not_pruned(store, self.changelog.as_bytes(), &[], height)?;
+        self.snapshot_item().may_load_at_height(store, height)
+    }
+
+    pub fn update<A, E>(&self, store: &mut dyn Storage, height: u64, action: A) -> Result<T, E>
+    where
+        A: FnOnce(Option<T>) -> Result<T, E>,
+        E: From<StdError>,
+    {
+        self.snapshot_item().update(store, height, action)
+    }
+
+    /// Remove changelog written before `height`, returns the number of removed entries.
+    ///
+    /// History below `height` is lost, `may_load_at_height` fails below it from now on.
+    pub fn prune(&self, store: &mut dyn Storage, height: u64) -> StdResult<usize> {
+        prune_changelog(store, self.changelog.as_bytes(), &[], height)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::{testing::MockStorage, Addr};
+
+    #[test]
+    fn new_owned() {
+        let mut storage = MockStorage::new();
+        let item: SnapshotItemCow<Addr> = SnapshotItemCow::new_owned(
+            String::from("admin"),
+            String::from("admin__checkpoints"),
+            String::from("admin__changelog"),
+            Strategy::EveryBlock,
+        );
+
+        item.save(&mut storage, &Addr::unchecked("a"), 10).unwrap();
+        item.save(&mut storage, &Addr::unchecked("b"), 20).unwrap();
+
+        assert_eq!(item.may_load_at_height(&storage, 10).unwrap(), None);
+        assert_eq!(
+            item.may_load_at_height(&storage, 20).unwrap(),
+            Some(Addr::unchecked("a"))
+        );
+        assert_eq!(item.load(&storage).unwrap(), Addr::unchecked("b"));
+    }
+
+    #[test]
+    fn selected_and_prune() {
+        let mut storage = MockStorage::new();
+        const CONFIG: SnapshotItemCow<u64> = SnapshotItemCow::new_ref(
+            "config",
+            "config__checkpoints",
+            "config__changelog",
+            Strategy::Selected,
+        );
+
+        for height in 1..=5 {
+            CONFIG.add_checkpoint(&mut storage, height).unwrap();
+            CONFIG.save(&mut storage, &height, height).unwrap();
+        }
+        CONFIG.save(&mut storage, &6, 6).unwrap();
+        CONFIG.may_load_at_height(&storage, 6).unwrap_err();
+
+        assert_eq!(CONFIG.may_load_at_height(&storage, 3).unwrap(), Some(2));
+        assert_eq!(CONFIG.prune(&mut storage, 3).unwrap(), 2);
+        assert_eq!(CONFIG.may_load_at_height(&storage, 3).unwrap(), Some(2));
+        assert_eq!(
+            CONFIG
+                .may_load_at_height(&storage, 2)
+                .unwrap_err()
+                .to_string(),
+            "Generic error: History before height 3 was pruned"
+        );
+        assert_eq!(CONFIG.may_load_at_height(&storage, 5).unwrap(), Some(4));
+        assert_eq!(CONFIG.load(&storage).unwrap(), 6);
+    }
+}
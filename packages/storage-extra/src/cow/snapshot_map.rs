@@ -0,0 +1,295 @@
+use cosmwasm_std::{Order, Pair, StdError, StdResult, Storage};
+use cw_storage_plus::{Bound, Prefix, Prefixer, PrimaryKey, SnapshotMap, Strategy};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{borrow::Cow, marker::PhantomData};
+
+use super::helpers::{assert_not_pruned, prune_changelog};
+
+/// Like `SnapshotMap` from `cw-storage-plus` but in `Cow`.
+#[derive(Debug, Clone)]
+pub struct SnapshotMapCow<'a, K, T> {
+    pub(crate) namespace: Cow<'a, str>,
+    pub(crate) checkpoints: Cow<'a, str>,
+    pub(crate) changelog: Cow<'a, str>,
+    strategy: Strategy,
+    key_type: PhantomData<K>,
+    data_type: PhantomData<T>,
+}
+
+impl<'a, 'k, K, T> SnapshotMapCow<'a, K, T>
+where
+    'k: 'a,
+{
+    pub const fn new_owned(
+        namespace: String,
+        checkpoints: String,
+        changelog: String,
+        strategy: Strategy,
+    ) -> Self {
+        Self {
+            namespace: Cow::Owned(namespace),
+            checkpoints: Cow::Owned(checkpoints),
+            changelog: Cow::Owned(changelog),
+            strategy,
+            key_type: PhantomData,
+            data_type: PhantomData,
+        }
+    }
+
+    pub const fn new_ref(
+        namespace: &'k str,
+        checkpoints: &'k str,
+        changelog: &'k str,
+        strategy: Strategy,
+    ) -> Self {
+        Self {
+            namespace: Cow::Borrowed(namespace),
+            checkpoints: Cow::Borrowed(checkpoints),
+            changelog: Cow::Borrowed(changelog),
+            strategy,
+            key_type: PhantomData,
+            data_type: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'key, K, T> SnapshotMapCow<'a, K, T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+    K: PrimaryKey<'key> + Prefixer<'key>,
+    'key: 'a,
+{
+    pub fn snapshot_map(&self) -> SnapshotMap<'_, K, T> {
+        SnapshotMap::new(
+            &self.namespace,
+            &self.checkpoints,
+            &self.changelog,
+            self.strategy,
+        )
+    }
+
+    pub fn add_checkpoint(&'key self, store: &mut dyn Storage, height: u64) -> StdResult<()> {
+        self.snapshot_map().add_checkpoint(store, height)
+    }
+
+    pub fn remove_checkpoint(&'key self, store: &mut dyn Storage, height: u64) -> StdResult<()> {
+        self.snapshot_map().remove_checkpoint(store, height)
+    }
+
+    pub fn assert_checkpointed(&'key self, store: &dyn Storage, height: u64) -> StdResult<()> {
+        self.snapshot_map().assert_checkpointed(store, height)
+    }
+
+    pub fn prefix(&'key self, p: K::Prefix) -> Prefix<T> {
+        self.snapshot_map().prefix(p)
+    }
+
+    pub fn sub_prefix(&'key self, p: K::SubPrefix) -> Prefix<T> {
+        self.snapshot_map().sub_prefix(p)
+    }
+
+    pub fn save(&'key self, store: &mut dyn Storage, k: K, data: &T, height: u64) -> StdResult<()> {
+        self.snapshot_map().save(store, k, data, height)
+    }
+
+    pub fn remove(&'key self, store: &mut dyn Storage, k: K, height: u64) -> StdResult<()> {
+        self.snapshot_map().remove(store, k, height)
+    }
+
+    pub fn load(&'key self, store: &dyn Storage, k: K) -> StdResult<T> {
+        self.snapshot_map().load(store, k)
+    }
+
+    pub fn may_load(&'key self, store: &dyn Storage, k: K) -> StdResult<Option<T>> {
+        self.snapshot_map().may_load(store, k)
+    }
+
+    /// Value of `k` at the start of block `height`. Any height is available with
+    /// `Strategy::EveryBlock`, only checkpointed ones with `Strategy::Selected` and none with
+    /// `Strategy::Never`. Fails below the height `k` was pruned to.
+    pub fn may_load_at_height(
+        &'key self,
+        store: &dyn Storage,
+        k: K,
+        height: u64,
+    ) -> StdResult<Option<T>> {
+        assert_not_pruned(store, self.changelog.as_bytes(), &k.prefix(), height)?;
+        self.snapshot_map().may_load_at_height(store, k, height)
+    }
+
+    pub fn update<A, E>(
+        &'key self,
+        store: &mut dyn Storage,
+        k: K,
+        height: u64,
+        action: A,
+    ) -> Result<T, E>
+    where
+        A: FnOnce(Option<T>) -> Result<T, E>,
+        E: From<StdError>,
+    {
+        self.snapshot_map().update(store, k, height, action)
+    }
+
+    /// Remove changelog of `k` written before `height`, returns the number of removed entries.
+    ///
+    /// History of `k` below `height` is lost, `may_load_at_height` fails below it from now on.
+    pub fn prune(&'key self, store: &mut dyn Storage, k: K, height: u64) -> StdResult<usize> {
+        prune_changelog(store, self.changelog.as_bytes(), &k.prefix(), height)
+    }
+
+    /// Range through the whole map with raw keys.
+    pub fn range_raw<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = StdResult<Pair<T>>> + 'c>
+    where
+        T: 'c,
+    {
+        Prefix::new(self.namespace.as_bytes(), &[]).range(store, min, max, order)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cow::MapCow;
+    use cosmwasm_std::{testing::MockStorage, Addr};
+
+    const BALANCES: SnapshotMapCow<&Addr, u64> = SnapshotMapCow::new_ref(
+        "balance",
+        "balance__checkpoints",
+        "balance__changelog",
+        Strategy::EveryBlock,
+    );
+
+    #[test]
+    fn every_block() {
+        let mut storage = MockStorage::new();
+        let a = Addr::unchecked("a");
+
+        BALANCES.save(&mut storage, &a, &1, 10).unwrap();
+        BALANCES.save(&mut storage, &a, &2, 20).unwrap();
+        // only the value before the first write of a block is kept
+        BALANCES.save(&mut storage, &a, &3, 20).unwrap();
+        BALANCES.remove(&mut storage, &a, 30).unwrap();
+
+        let at = |height| BALANCES.may_load_at_height(&storage, &a, height).unwrap();
+        assert_eq!(at(5), None);
+        assert_eq!(at(10), None);
+        assert_eq!(at(11), Some(1));
+        assert_eq!(at(20), Some(1));
+        assert_eq!(at(21), Some(3));
+        assert_eq!(at(31), None);
+        assert_eq!(BALANCES.may_load(&storage, &a).unwrap(), None);
+    }
+
+    #[test]
+    fn selected() {
+        let mut storage = MockStorage::new();
+        let a = Addr::unchecked("a");
+        let map: SnapshotMapCow<&Addr, u64> = SnapshotMapCow::new_owned(
+            "pool_balance".to_string(),
+            "pool_balance__checkpoints".to_string(),
+            "pool_balance__changelog".to_string(),
+            Strategy::Selected,
+        );
+
+        // no checkpoint, no history
+        map.save(&mut storage, &a, &1, 10).unwrap();
+        map.add_checkpoint(&mut storage, 20).unwrap();
+        map.save(&mut storage, &a, &2, 20).unwrap();
+        map.save(&mut storage, &a, &3, 25).unwrap();
+
+        map.assert_checkpointed(&storage, 10).unwrap_err();
+        map.may_load_at_height(&storage, &a, 10).unwrap_err();
+        assert_eq!(map.may_load_at_height(&storage, &a, 20).unwrap(), Some(1));
+        assert_eq!(map.load(&storage, &a).unwrap(), 3);
+
+        map.remove_checkpoint(&mut storage, 20).unwrap();
+        map.assert_checkpointed(&storage, 20).unwrap_err();
+    }
+
+    #[test]
+    fn never() {
+        let mut storage = MockStorage::new();
+        let a = Addr::unchecked("a");
+        const MAP: SnapshotMapCow<&Addr, u64> = SnapshotMapCow::new_ref(
+            "never",
+            "never__checkpoints",
+            "never__changelog",
+            Strategy::Never,
+        );
+
+        MAP.save(&mut storage, &a, &1, 10).unwrap();
+        MAP.update(&mut storage, &a, 20, |v| -> StdResult<_> {
+            Ok(v.unwrap_or_default() + 1)
+        })
+        .unwrap();
+
+        // no history is kept, even at a checkpoint
+        MAP.add_checkpoint(&mut storage, 10).unwrap();
+        MAP.may_load_at_height(&storage, &a, 10).unwrap_err();
+        assert_eq!(MAP.load(&storage, &a).unwrap(), 2);
+        assert_eq!(MAP.prune(&mut storage, &a, 100).unwrap(), 0);
+    }
+
+    #[test]
+    fn prune() {
+        let mut storage = MockStorage::new();
+        let a = Addr::unchecked("a");
+        let b = Addr::unchecked("b");
+
+        for height in 1..=5 {
+            BALANCES.save(&mut storage, &a, &height, height).unwrap();
+            BALANCES.save(&mut storage, &b, &height, height).unwrap();
+        }
+
+        assert_eq!(BALANCES.prune(&mut storage, &a, 4).unwrap(), 3);
+        assert_eq!(BALANCES.prune(&mut storage, &a, 4).unwrap(), 0);
+
+        // pruned history is an error instead of a later value
+        assert_eq!(
+            BALANCES
+                .may_load_at_height(&storage, &a, 3)
+                .unwrap_err()
+                .to_string(),
+            "Generic error: History before height 4 was pruned"
+        );
+        // pruning below does not bring history back
+        assert_eq!(BALANCES.prune(&mut storage, &a, 2).unwrap(), 0);
+        BALANCES.may_load_at_height(&storage, &a, 3).unwrap_err();
+
+        // a map named like the changelog with a suffix does not hold pruned heights
+        let suffixed: MapCow<&[u8], u64> = MapCow::new_ref("balance__changelog__pruned");
+        suffixed.save(&mut storage, b"\0\x01b", &5).unwrap();
+        assert_eq!(
+            BALANCES.may_load_at_height(&storage, &b, 2).unwrap(),
+            Some(1)
+        );
+
+        // history from height onwards is untouched
+        assert_eq!(
+            BALANCES.may_load_at_height(&storage, &a, 4).unwrap(),
+            Some(3)
+        );
+        assert_eq!(
+            BALANCES.may_load_at_height(&storage, &a, 5).unwrap(),
+            Some(4)
+        );
+        // other keys keep their history
+        assert_eq!(
+            BALANCES.may_load_at_height(&storage, &b, 2).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            BALANCES
+                .range_raw(&storage, None, None, Order::Ascending)
+                .count(),
+            2
+        );
+    }
+}
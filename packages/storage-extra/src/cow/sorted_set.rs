@@ -0,0 +1,628 @@
+use cosmwasm_std::{to_vec, Order, StdError, StdResult, Storage, Uint128};
+use cw_storage_plus::{Bound, Prefix, PrimaryKey};
+use std::{borrow::Cow, convert::TryInto, marker::PhantomData};
+
+use super::helpers::namespaces_with_key;
+use super::keys::{KeyDeserialize, PrefixBound};
+
+/// Score of a `SortedSetCow`, encoded to fixed length bytes ordering like the score itself.
+pub trait Score: Sized {
+    fn to_sort_key(&self) -> Vec<u8>;
+
+    fn from_sort_key(key: &[u8]) -> StdResult<Self>;
+}
+
+macro_rules! unsigned_score {
+    ($($t:ty),*) => {
+        $(impl Score for $t {
+            fn to_sort_key(&self) -> Vec<u8> {
+                self.to_be_bytes().to_vec()
+            }
+
+            fn from_sort_key(key: &[u8]) -> StdResult<Self> {
+                key.try_into()
+                    .map(<$t>::from_be_bytes)
+                    .map_err(|_| StdError::generic_err("Invalid score length"))
+            }
+        })*
+    };
+}
+
+macro_rules! signed_score {
+    ($($t:ty),*) => {
+        $(impl Score for $t {
+            // flip the sign bit, so negative scores sort before positive ones
+            fn to_sort_key(&self) -> Vec<u8> {
+                let mut key = self.to_be_bytes();
+                key[0] ^= 0x80;
+                key.to_vec()
+            }
+
+            fn from_sort_key(key: &[u8]) -> StdResult<Self> {
+                let mut key: [u8; std::mem::size_of::<$t>()] = key
+                    .try_into()
+                    .map_err(|_| StdError::generic_err("Invalid score length"))?;
+                key[0] ^= 0x80;
+                Ok(<$t>::from_be_bytes(key))
+            }
+        })*
+    };
+}
+
+unsigned_score!(u8, u16, u32, u64, u128);
+signed_score!(i8, i16, i32, i64, i128);
+
+impl Score for Uint128 {
+    fn to_sort_key(&self) -> Vec<u8> {
+        self.u128().to_sort_key()
+    }
+
+    fn from_sort_key(key: &[u8]) -> StdResult<Self> {
+        u128::from_sort_key(key).map(Uint128::new)
+    }
+}
+
+/// Smallest key above every key starting with `key`, `None` if `key` is the highest one.
+fn next_sort_key(key: &[u8]) -> Option<Vec<u8>> {
+    let mut next = key.to_vec();
+    for byte in next.iter_mut().rev() {
+        if *byte == u8::MAX {
+            *byte = 0;
+        } else {
+            *byte += 1;
+            return Some(next);
+        }
+    }
+    None
+}
+
+/// Length of the score part of a `(score, member)` entry key.
+fn score_len(key: &[u8], member_len: u32) -> StdResult<usize> {
+    key.len()
+        .checked_sub(member_len as usize)
+        .ok_or_else(|| StdError::generic_err("Score key is shorter than its member"))
+}
+
+/// Set of members ordered by score, ties ordered by ascending member.
+///
+/// Score of each member is kept under `member_namespace`, while `(score, member)` entries are kept
+/// under `score_namespace` for ordered iteration.
+#[derive(Debug, Clone)]
+pub struct SortedSetCow<'a, M, S> {
+    pub(crate) member_namespace: Cow<'a, str>,
+    pub(crate) score_namespace: Cow<'a, str>,
+    member_type: PhantomData<M>,
+    score_type: PhantomData<S>,
+}
+
+impl<'a, 'k, M, S> SortedSetCow<'a, M, S>
+where
+    'k: 'a,
+{
+    pub const fn new_owned(member_namespace: String, score_namespace: String) -> Self {
+        Self {
+            member_namespace: Cow::Owned(member_namespace),
+            score_namespace: Cow::Owned(score_namespace),
+            member_type: PhantomData,
+            score_type: PhantomData,
+        }
+    }
+
+    pub const fn new_ref(member_namespace: &'k str, score_namespace: &'k str) -> Self {
+        Self {
+            member_namespace: Cow::Borrowed(member_namespace),
+            score_namespace: Cow::Borrowed(score_namespace),
+            member_type: PhantomData,
+            score_type: PhantomData,
+        }
+    }
+}
+
+impl<'a, M, S> SortedSetCow<'a, M, S>
+where
+    M: PrimaryKey<'a> + KeyDeserialize,
+    S: Score,
+{
+    fn member_key(&self, member: &[u8]) -> Vec<u8> {
+        namespaces_with_key(&[self.member_namespace.as_bytes()], member)
+    }
+
+    fn score_key(&self, score: &[u8], member: &[u8]) -> Vec<u8> {
+        namespaces_with_key(
+            &[self.score_namespace.as_bytes()],
+            &[score, member].concat(),
+        )
+    }
+
+    fn scores(&self) -> Prefix<u32> {
+        Prefix::new(self.score_namespace.as_bytes(), &[])
+    }
+
+    /// Insert `member` or move it to `score`.
+    pub fn insert(&self, store: &mut dyn Storage, member: M, score: &S) -> StdResult<()> {
+        let member = member.joined_key();
+        self.remove_raw(store, &member);
+
+        let score = score.to_sort_key();
+        store.set(&self.member_key(&member), &score);
+        store.set(
+            &self.score_key(&score, &member),
+            &to_vec(&(member.len() as u32))?,
+        );
+        Ok(())
+    }
+
+    /// Remove `member`, returns whether it was in the set.
+    pub fn remove(&self, store: &mut dyn Storage, member: M) -> bool {
+        self.remove_raw(store, &member.joined_key())
+    }
+
+    fn remove_raw(&self, store: &mut dyn Storage, member: &[u8]) -> bool {
+        match store.get(&self.member_key(member)) {
+            Some(score) => {
+                store.remove(&self.score_key(&score, member));
+                store.remove(&self.member_key(member));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn score(&self, store: &dyn Storage, member: M) -> StdResult<Option<S>> {
+        store
+            .get(&self.member_key(&member.joined_key()))
+            .map(|score| S::from_sort_key(&score))
+            .transpose()
+    }
+
+    /// Zero based position of `member` in given order, `None` if not in the set.
+    ///
+    /// Counts every preceding member, so it costs as many reads as the rank.
+    pub fn rank(&self, store: &dyn Storage, member: M, order: Order) -> StdResult<Option<u32>> {
+        let member = member.joined_key();
+        let score = match store.get(&self.member_key(&member)) {
+            Some(score) => score,
+            None => return Ok(None),
+        };
+
+        // ties stay ascending by member in both orders
+        let same_score_before = self
+            .scores()
+            .keys(
+                store,
+                Some(Bound::Inclusive(score.clone())),
+                Some(Bound::Exclusive([score.as_slice(), &member].concat())),
+                Order::Ascending,
+            )
+            .count();
+        let other_scores_before = match order {
+            Order::Ascending => self
+                .scores()
+                .keys(
+                    store,
+                    None,
+                    Some(Bound::Exclusive(score.clone())),
+                    Order::Ascending,
+                )
+                .count(),
+            Order::Descending => match next_sort_key(&score) {
+                Some(next) => self
+                    .scores()
+                    .keys(store, Some(Bound::Inclusive(next)), None, Order::Ascending)
+                    .count(),
+                None => 0,
+            },
+        };
+
+        Ok(Some((same_score_before + other_scores_before) as u32))
+    }
+
+    /// Up to `limit` members with the highest scores, highest first.
+    pub fn top_n(&self, store: &dyn Storage, limit: usize) -> StdResult<Vec<(M::Output, S)>> {
+        self.range_by_score(store, None, None, None, Order::Descending, limit)
+    }
+
+    /// Up to `limit` members with the lowest scores, lowest first.
+    pub fn bottom_n(&self, store: &dyn Storage, limit: usize) -> StdResult<Vec<(M::Output, S)>> {
+        self.range_by_score(store, None, None, None, Order::Ascending, limit)
+    }
+
+    /// Up to `limit` members with a score within given bounds, ordered by score in given order,
+    /// then by ascending member.
+    ///
+    /// `start_after` is the last `(member, score)` of a previous page, it does not need to be in
+    /// the set anymore.
+    pub fn range_by_score(
+        &self,
+        store: &dyn Storage,
+        min: Option<PrefixBound<S>>,
+        max: Option<PrefixBound<S>>,
+        start_after: Option<(M, S)>,
+        order: Order,
+        limit: usize,
+    ) -> StdResult<Vec<(M::Output, S)>> {
+        // inclusive lower and exclusive upper raw bound
+        let min = match min {
+            None => None,
+            Some(PrefixBound::Inclusive(s)) => Some(s.to_sort_key()),
+            Some(PrefixBound::Exclusive(s)) => match next_sort_key(&s.to_sort_key()) {
+                Some(next) => Some(next),
+                None => return Ok(vec![]),
+            },
+        };
+        let max = match max {
+            None => None,
+            Some(PrefixBound::Inclusive(s)) => next_sort_key(&s.to_sort_key()),
+            Some(PrefixBound::Exclusive(s)) => Some(s.to_sort_key()),
+        };
+        let start_after =
+            start_after.map(|(member, score)| (score.to_sort_key(), member.joined_key()));
+
+        let entries = match order {
+            Order::Ascending => {
+                // (score, member) keys are ascending already
+                let min = match start_after {
+                    Some((score, member)) => {
+                        // smallest key above the entry
+                        let after = [score.as_slice(), &member, &[0]].concat();
+                        Some(match min {
+                            Some(min) => min.max(after),
+                            None => after,
+                        })
+                    }
+                    None => min,
+                };
+                self.scores()
+                    .range(
+                        store,
+                        min.map(Bound::Inclusive),
+                        max.map(Bound::Exclusive),
+                        order,
+                    )
+                    .take(limit)
+                    .collect::<StdResult<Vec<_>>>()?
+            }
+            Order::Descending => self.range_descending(store, min, max, start_after, limit)?,
+        };
+
+        entries
+            .into_iter()
+            .map(|(key, member_len)| {
+                let split = score_len(&key, member_len)?;
+                Ok((
+                    M::from_vec(key[split..].to_vec())?,
+                    S::from_sort_key(&key[..split])?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Walk groups of equal scores from the highest one below `max`, reading each group by
+    /// ascending member, so at most `limit` entries and one lookup per group are read.
+    fn range_descending(
+        &self,
+        store: &dyn Storage,
+        min: Option<Vec<u8>>,
+        mut max: Option<Vec<u8>>,
+        start_after: Option<(Vec<u8>, Vec<u8>)>,
+        limit: usize,
+    ) -> StdResult<Vec<(Vec<u8>, u32)>> {
+        let mut entries = vec![];
+
+        // rest of the group of `start_after`, then only lower scores
+        if let Some((score, member)) = start_after {
+            let below_max = match &max {
+                Some(max) => score < *max,
+                None => true,
+            };
+            if min.as_ref() <= Some(&score) && below_max {
+                let after = [score.as_slice(), &member].concat();
+                entries = self.read_group(store, &score, Bound::Exclusive(after), limit)?;
+            }
+            max = Some(match max {
+                Some(max) => max.min(score),
+                None => score,
+            });
+        }
+
+        while entries.len() < limit {
+            let highest = self
+                .scores()
+                .range(
+                    store,
+                    min.clone().map(Bound::Inclusive),
+                    max.clone().map(Bound::Exclusive),
+                    Order::Descending,
+                )
+                .next();
+            let score = match highest {
+                Some(entry) => {
+                    let (key, member_len) = entry?;
+                    key[..score_len(&key, member_len)?].to_vec()
+                }
+                None => break,
+            };
+
+            let group = self.read_group(
+                store,
+                &score,
+                Bound::Inclusive(score.clone()),
+                limit - entries.len(),
+            )?;
+            entries.extend(group);
+            max = Some(score);
+        }
+
+        Ok(entries)
+    }
+
+    /// Up to `limit` entries of `score` from `start`, by ascending member.
+    fn read_group(
+        &self,
+        store: &dyn Storage,
+        score: &[u8],
+        start: Bound,
+        limit: usize,
+    ) -> StdResult<Vec<(Vec<u8>, u32)>> {
+        self.scores()
+            .range(
+                store,
+                Some(start),
+                next_sort_key(score).map(Bound::Exclusive),
+                Order::Ascending,
+            )
+            .take(limit)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::{testing::MockStorage, Addr};
+
+    const BOARD: SortedSetCow<&Addr, u64> = SortedSetCow::new_ref("board", "board__score");
+
+    fn addr(s: &str) -> Addr {
+        Addr::unchecked(s)
+    }
+
+    fn named(entries: Vec<(Addr, u64)>) -> Vec<(String, u64)> {
+        entries
+            .into_iter()
+            .map(|(a, s)| (a.to_string(), s))
+            .collect()
+    }
+
+    fn setup(storage: &mut MockStorage) {
+        for (name, score) in [("c", 20), ("a", 10), ("d", 20), ("b", 20), ("e", 30)] {
+            BOARD.insert(storage, &addr(name), &score).unwrap();
+        }
+    }
+
+    #[test]
+    fn top_and_bottom() {
+        let mut storage = MockStorage::new();
+        setup(&mut storage);
+
+        assert_eq!(
+            named(BOARD.top_n(&storage, 3).unwrap()),
+            vec![
+                ("e".to_string(), 30),
+                ("b".to_string(), 20),
+                ("c".to_string(), 20)
+            ]
+        );
+        assert_eq!(
+            named(BOARD.bottom_n(&storage, 3).unwrap()),
+            vec![
+                ("a".to_string(), 10),
+                ("b".to_string(), 20),
+                ("c".to_string(), 20)
+            ]
+        );
+        assert_eq!(BOARD.top_n(&storage, 10).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn insert_moves_and_remove() {
+        let mut storage = MockStorage::new();
+        setup(&mut storage);
+
+        BOARD.insert(&mut storage, &addr("a"), &40).unwrap();
+        assert_eq!(BOARD.score(&storage, &addr("a")).unwrap(), Some(40));
+        assert_eq!(
+            named(BOARD.top_n(&storage, 1).unwrap()),
+            vec![("a".to_string(), 40)]
+        );
+
+        assert!(BOARD.remove(&mut storage, &addr("a")));
+        assert!(!BOARD.remove(&mut storage, &addr("a")));
+        assert_eq!(BOARD.score(&storage, &addr("a")).unwrap(), None);
+        assert_eq!(
+            BOARD.rank(&storage, &addr("a"), Order::Ascending).unwrap(),
+            None
+        );
+        assert_eq!(BOARD.bottom_n(&storage, 10).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn rank() {
+        let mut storage = MockStorage::new();
+        setup(&mut storage);
+
+        let rank = |name, order| BOARD.rank(&storage, &addr(name), order).unwrap();
+        assert_eq!(rank("a", Order::Ascending), Some(0));
+        assert_eq!(rank("c", Order::Ascending), Some(2));
+        assert_eq!(rank("e", Order::Ascending), Some(4));
+        assert_eq!(rank("e", Order::Descending), Some(0));
+        assert_eq!(rank("b", Order::Descending), Some(1));
+        assert_eq!(rank("d", Order::Descending), Some(3));
+        assert_eq!(rank("a", Order::Descending), Some(4));
+    }
+
+    #[test]
+    fn range_by_score() {
+        let mut storage = MockStorage::new();
+        setup(&mut storage);
+
+        assert_eq!(
+            named(
+                BOARD
+                    .range_by_score(
+                        &storage,
+                        Some(PrefixBound::Exclusive(10)),
+                        Some(PrefixBound::Inclusive(20)),
+                        None,
+                        Order::Descending,
+                        10,
+                    )
+                    .unwrap()
+            ),
+            vec![
+                ("b".to_string(), 20),
+                ("c".to_string(), 20),
+                ("d".to_string(), 20)
+            ]
+        );
+        assert_eq!(
+            named(
+                BOARD
+                    .range_by_score(
+                        &storage,
+                        Some(PrefixBound::Inclusive(20)),
+                        Some(PrefixBound::Exclusive(30)),
+                        None,
+                        Order::Ascending,
+                        2,
+                    )
+                    .unwrap()
+            ),
+            vec![("b".to_string(), 20), ("c".to_string(), 20)]
+        );
+        assert_eq!(
+            BOARD
+                .range_by_score(
+                    &storage,
+                    Some(PrefixBound::Exclusive(u64::MAX)),
+                    None,
+                    None,
+                    Order::Ascending,
+                    10,
+                )
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn paginate_ties() {
+        let mut storage = MockStorage::new();
+        setup(&mut storage);
+
+        let page = |storage: &MockStorage, start_after: Option<(&str, u64)>, order| {
+            let start_after = start_after.map(|(name, score)| (addr(name), score));
+            named(
+                BOARD
+                    .range_by_score(
+                        storage,
+                        None,
+                        None,
+                        start_after.as_ref().map(|(a, s)| (a, *s)),
+                        order,
+                        2,
+                    )
+                    .unwrap(),
+            )
+        };
+
+        // pages split a group of ties, which stays ascending by member
+        assert_eq!(
+            page(&storage, None, Order::Descending),
+            vec![("e".to_string(), 30), ("b".to_string(), 20)]
+        );
+        assert_eq!(
+            page(&storage, Some(("b", 20)), Order::Descending),
+            vec![("c".to_string(), 20), ("d".to_string(), 20)]
+        );
+        assert_eq!(
+            page(&storage, Some(("d", 20)), Order::Descending),
+            vec![("a".to_string(), 10)]
+        );
+        assert_eq!(
+            page(&storage, Some(("b", 20)), Order::Ascending),
+            vec![("c".to_string(), 20), ("d".to_string(), 20)]
+        );
+
+        // last entry of a page may be gone since
+        BOARD.remove(&mut storage, &addr("c"));
+        assert_eq!(
+            page(&storage, Some(("c", 20)), Order::Descending),
+            vec![("d".to_string(), 20), ("a".to_string(), 10)]
+        );
+
+        // start after a score above the bounds
+        assert_eq!(
+            named(
+                BOARD
+                    .range_by_score(
+                        &storage,
+                        None,
+                        Some(PrefixBound::Exclusive(30)),
+                        Some((&addr("e"), 30)),
+                        Order::Descending,
+                        10,
+                    )
+                    .unwrap()
+            ),
+            vec![
+                ("b".to_string(), 20),
+                ("d".to_string(), 20),
+                ("a".to_string(), 10)
+            ]
+        );
+
+        // start after does not widen given bounds
+        assert_eq!(
+            named(
+                BOARD
+                    .range_by_score(
+                        &storage,
+                        Some(PrefixBound::Inclusive(20)),
+                        None,
+                        Some((&addr("a"), 10)),
+                        Order::Ascending,
+                        10,
+                    )
+                    .unwrap()
+            ),
+            vec![
+                ("b".to_string(), 20),
+                ("d".to_string(), 20),
+                ("e".to_string(), 30)
+            ]
+        );
+    }
+
+    #[test]
+    fn signed_scores() {
+        let mut storage = MockStorage::new();
+        let set: SortedSetCow<&str, i64> =
+            SortedSetCow::new_owned("pool_pnl".to_string(), "pool_pnl__score".to_string());
+
+        set.insert(&mut storage, "loss", &-5).unwrap();
+        set.insert(&mut storage, "min", &i64::MIN).unwrap();
+        set.insert(&mut storage, "gain", &7).unwrap();
+        set.insert(&mut storage, "flat", &0).unwrap();
+
+        assert_eq!(
+            set.bottom_n(&storage, 4).unwrap(),
+            vec![
+                ("min".to_string(), i64::MIN),
+                ("loss".to_string(), -5),
+                ("flat".to_string(), 0),
+                ("gain".to_string(), 7)
+            ]
+        );
+    }
+}
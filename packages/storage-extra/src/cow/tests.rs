@@ -1,5 +1,5 @@
-use cosmwasm_std::{testing::MockStorage, Addr, Order};
-use cw_storage_plus::{Bound, Index, IndexList, U64Key};
+use cosmwasm_std::{testing::MockStorage, Addr, Binary, Order, StdResult};
+use cw_storage_plus::{Bound, Index, IndexList, PrimaryKey, U64Key};
 use serde::{Deserialize, Serialize};
 
 use super::*;
@@ -58,13 +58,13 @@ fn correct_namespace() {
     assert_eq!(it.item.namespace, "primary-item");
     assert_eq!(it.map.namespace, "primary-map");
     assert_eq!(it.indexed_map.pk_namespace, "primary-idm");
-    assert_eq!(it.indexed_map.index.count.pk_namespace, "primary-idm");
+    assert_eq!(it.indexed_map.index.count.pk_namespace(), "primary-idm");
     assert_eq!(
-        it.indexed_map.index.count.idx_namespace,
+        it.indexed_map.index.count.idx_namespace(),
         "primary-idm-count"
     );
     assert_eq!(
-        it.indexed_map.index.address.idx_namespace,
+        it.indexed_map.index.address.idx_namespace(),
         "primary-idm-addr"
     );
 }
@@ -319,3 +319,578 @@ fn indexed_map_works() {
         None
     );
 }
+
+#[test]
+fn indexed_map_range_and_paginate() {
+    let mut storage = MockStorage::new();
+    let it = ItemMapAccessor::new("primary");
+
+    let records = (0..5u64)
+        .map(|id| ToIndex {
+            id,
+            count: id % 2,
+            address: Addr::unchecked(format!("addr{}", id)),
+        })
+        .collect::<Vec<_>>();
+
+    for record in records.iter() {
+        it.indexed_map
+            .save(&mut storage, record.id.into(), record)
+            .unwrap();
+    }
+
+    assert_eq!(
+        it.indexed_map
+            .range(&storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap(),
+        records
+            .iter()
+            .map(|e| (e.id, e.clone()))
+            .collect::<Vec<_>>()
+    );
+
+    assert_eq!(
+        it.indexed_map
+            .keys(
+                &storage,
+                Some(Bound::exclusive_int(2u64)),
+                None,
+                Order::Ascending
+            )
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap(),
+        vec![3, 4]
+    );
+
+    let page = it
+        .indexed_map
+        .paginate(&storage, None, 2, Order::Ascending)
+        .unwrap();
+    assert_eq!(page.iter().map(|e| e.0).collect::<Vec<_>>(), vec![0, 1]);
+
+    let page = it
+        .indexed_map
+        .paginate(&storage, Some(page[1].0.into()), 2, Order::Ascending)
+        .unwrap();
+    assert_eq!(page.iter().map(|e| e.0).collect::<Vec<_>>(), vec![2, 3]);
+
+    let page = it
+        .indexed_map
+        .paginate(&storage, Some(3.into()), 10, Order::Descending)
+        .unwrap();
+    assert_eq!(
+        page.iter().map(|e| e.1.clone()).collect::<Vec<_>>(),
+        vec![records[2].clone(), records[1].clone(), records[0].clone()]
+    );
+}
+
+#[test]
+fn indexed_map_clear() {
+    let mut storage = MockStorage::new();
+    let it = ItemMapAccessor::new("primary");
+
+    for id in 0..5u64 {
+        let record = ToIndex {
+            id,
+            count: 5,
+            address: Addr::unchecked(format!("addr{}", id)),
+        };
+        it.indexed_map
+            .save(&mut storage, record.id.into(), &record)
+            .unwrap();
+    }
+
+    assert_eq!(
+        it.indexed_map
+            .clear(&mut storage, 0)
+            .unwrap_err()
+            .to_string(),
+        "Generic error: Clear limit must be greater than zero"
+    );
+    assert_eq!(
+        it.indexed_map.clear(&mut storage, 2).unwrap(),
+        ClearProgress {
+            removed: 2,
+            done: false
+        }
+    );
+    assert_eq!(
+        it.indexed_map
+            .index
+            .count
+            .prefix(5.into())
+            .keys(&storage, None, None, Order::Ascending)
+            .count(),
+        3
+    );
+    assert_eq!(
+        it.indexed_map
+            .index
+            .address
+            .item(&storage, Addr::unchecked("addr0"))
+            .unwrap(),
+        None
+    );
+
+    assert_eq!(
+        it.indexed_map.clear(&mut storage, usize::MAX).unwrap(),
+        ClearProgress {
+            removed: 3,
+            done: true
+        }
+    );
+    assert_eq!(
+        it.indexed_map
+            .index
+            .count
+            .prefix(5.into())
+            .keys(&storage, None, None, Order::Ascending)
+            .count(),
+        0
+    );
+    assert_eq!(
+        it.indexed_map
+            .index
+            .address
+            .item(&storage, Addr::unchecked("addr4"))
+            .unwrap(),
+        None
+    );
+}
+
+#[test]
+fn indexed_map_clear_prefix() {
+    let mut storage = MockStorage::new();
+    const POOL: IndexedMapCow<(&str, U64Key), ToIndex, ToIndexList> = IndexedMapCow::new_ref(
+        "pool",
+        ToIndexList {
+            count: MultiIndexCow::new_ref("pool", "pool_count", |e, k| (e.count.into(), k)),
+            address: UniqueIndexCow::new_ref("pool_address", |e| e.address.clone()),
+        },
+    );
+
+    for (pool, id) in [("a", 0u64), ("a", 1), ("a", 2), ("b", 3)] {
+        let record = ToIndex {
+            id,
+            count: 5,
+            address: Addr::unchecked(format!("addr{}", id)),
+        };
+        POOL.save(&mut storage, (pool, id.into()), &record).unwrap();
+    }
+
+    assert!(!POOL.clear_prefix(&mut storage, "a", 2).unwrap().done);
+    // exactly `limit` records left
+    assert_eq!(
+        POOL.clear_prefix(&mut storage, "a", 1).unwrap(),
+        ClearProgress {
+            removed: 1,
+            done: true
+        }
+    );
+
+    assert_eq!(
+        POOL.index
+            .count
+            .prefix(5.into())
+            .range(&storage, None, None, Order::Ascending)
+            .map(|e| e.unwrap().1.id)
+            .collect::<Vec<_>>(),
+        vec![3]
+    );
+    assert_eq!(
+        POOL.range(&storage, None, None, Order::Ascending)
+            .map(|e| e.unwrap().0)
+            .collect::<Vec<_>>(),
+        vec![("b".to_string(), 3)]
+    );
+    assert!(POOL
+        .index
+        .address
+        .item(&storage, Addr::unchecked("addr1"))
+        .unwrap()
+        .is_none());
+}
+
+#[derive(Clone)]
+struct ConditionalUniqueList<'a> {
+    count: MultiIndexCow<'a, (U64Key, Vec<u8>), ToIndex>,
+    address: UniqueIndexCow<'a, Addr, ToIndex>,
+}
+
+impl IndexList<ToIndex> for ConditionalUniqueList<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<ToIndex>> + '_> {
+        let v: Vec<&dyn Index<ToIndex>> = vec![&self.count, &self.address];
+        Box::new(v.into_iter())
+    }
+}
+
+fn index_keys<I>(index: &I, storage: &MockStorage) -> Vec<u64>
+where
+    I: IndexCow<ToIndex>,
+    I::Key: for<'k> PrimaryKey<'k, SubPrefix = ()>,
+{
+    index
+        .sub_prefix(())
+        .range(storage, None, None, Order::Ascending)
+        .map(|e| e.unwrap().1.id)
+        .collect()
+}
+
+#[test]
+fn index_builder_conditional_unique() {
+    let mut storage = MockStorage::new();
+    // only active (count > 0) records reserve their address
+    const ACTIVE: IndexedMapCow<U64Key, ToIndex, ConditionalUniqueList> = IndexedMapCow::new_ref(
+        "active",
+        ConditionalUniqueList {
+            count: IndexBuilder::new_ref("active", "active_count")
+                .with_cond(|e: &ToIndex| e.count > 0)
+                .multi(|e, k| (e.count.into(), k)),
+            address: IndexBuilder::new_ref("active", "active_address")
+                .with_cond(|e: &ToIndex| e.count > 0)
+                .unique(|e| e.address.clone()),
+        },
+    );
+
+    let record = |id, count| ToIndex {
+        id,
+        count,
+        address: Addr::unchecked("shared"),
+    };
+
+    ACTIVE.save(&mut storage, 0.into(), &record(0, 0)).unwrap();
+    ACTIVE.save(&mut storage, 1.into(), &record(1, 0)).unwrap();
+    ACTIVE.save(&mut storage, 2.into(), &record(2, 3)).unwrap();
+
+    let err = ACTIVE
+        .save(&mut storage, 3.into(), &record(3, 4))
+        .unwrap_err();
+    assert!(UniqueViolation::from_std_error(&err).is_some());
+
+    assert_eq!(index_keys(&ACTIVE.index.count, &storage), vec![2]);
+    assert_eq!(index_keys(&ACTIVE.index.address, &storage), vec![2]);
+    assert_eq!(
+        ACTIVE
+            .index
+            .address
+            .load_by(&storage, Addr::unchecked("shared"))
+            .unwrap()
+            .1,
+        record(2, 3)
+    );
+    assert_eq!(ACTIVE.index.address.pk_namespace(), "active");
+    assert_eq!(ACTIVE.index.count.idx_namespace(), "active_count");
+}
+
+#[test]
+fn indexed_map_rebuild_index() {
+    let mut storage = MockStorage::new();
+    const TO: IndexedMapCow<U64Key, ToIndex, ToIndexList> = IndexedMapCow::new_ref(
+        "backfill",
+        ToIndexList {
+            count: MultiIndexCow::new_ref("backfill", "backfill_count", |e, k| (e.count.into(), k)),
+            address: UniqueIndexCow::new_ref("backfill_address", |e| e.address.clone()),
+        },
+    );
+    // index added after data was written
+    const BY_ID: MultiIndexCow<(U64Key, Vec<u8>), ToIndex> =
+        MultiIndexCow::new_ref("backfill", "backfill_id", |e, k| (e.id.into(), k));
+
+    for id in 0..5u64 {
+        let record = ToIndex {
+            id,
+            count: id % 2,
+            address: Addr::unchecked(format!("addr{}", id)),
+        };
+        TO.save(&mut storage, id.into(), &record).unwrap();
+    }
+
+    let pk = |id: u64| U64Key::new(id).joined_key();
+    let report = TO.verify_index(&storage, &BY_ID).unwrap();
+    assert_eq!(report.missing.len(), 5);
+    assert!(TO
+        .verify_index(&storage, &TO.index.count)
+        .unwrap()
+        .is_consistent());
+
+    assert_eq!(
+        TO.rebuild_index(&mut storage, &BY_ID, None, 0)
+            .unwrap_err()
+            .to_string(),
+        "Generic error: Rebuild limit must be greater than zero"
+    );
+
+    let mut cursor = None;
+    let mut rounds = 0;
+    loop {
+        rounds += 1;
+        cursor = TO.rebuild_index(&mut storage, &BY_ID, cursor, 2).unwrap();
+        if cursor.is_none() {
+            break;
+        }
+    }
+    // 3 rounds over records, then 3 over entries
+    assert_eq!(rounds, 6);
+    assert!(TO.verify_index(&storage, &BY_ID).unwrap().is_consistent());
+
+    // entry of a removed record, and a record that lost its entry
+    BY_ID
+        .save(
+            &mut storage,
+            &pk(9),
+            &ToIndex {
+                id: 9,
+                count: 0,
+                address: Addr::unchecked("addr9"),
+            },
+        )
+        .unwrap();
+    let second = TO.load(&storage, 1.into()).unwrap();
+    BY_ID.remove(&mut storage, &pk(1), &second).unwrap();
+
+    let report = TO.verify_index(&storage, &BY_ID).unwrap();
+    assert_eq!(report.missing, vec![Binary::from(pk(1))]);
+    assert_eq!(
+        report.orphaned,
+        vec![Binary::from(BY_ID.index_key((9.into(), pk(9))))]
+    );
+}
+
+#[test]
+fn indexed_map_rebuild_index_repairs() {
+    let mut storage = MockStorage::new();
+    const TO: IndexedMapCow<U64Key, ToIndex, ToIndexList> = IndexedMapCow::new_ref(
+        "repair",
+        ToIndexList {
+            count: MultiIndexCow::new_ref("repair", "repair_count", |e, k| (e.count.into(), k)),
+            address: UniqueIndexCow::new_ref("repair_address", |e| e.address.clone()),
+        },
+    );
+    // same namespace, key function changed from id to count
+    const OLD: MultiIndexCow<(U64Key, Vec<u8>), ToIndex> =
+        MultiIndexCow::new_ref("repair", "repair_by", |e, k| (e.id.into(), k));
+    const NEW: MultiIndexCow<(U64Key, Vec<u8>), ToIndex> =
+        MultiIndexCow::new_ref("repair", "repair_by", |e, k| ((e.count + 10).into(), k));
+
+    let record = |id: u64| ToIndex {
+        id,
+        count: id % 2,
+        address: Addr::unchecked(format!("addr{}", id)),
+    };
+    for id in 0..5u64 {
+        TO.save(&mut storage, id.into(), &record(id)).unwrap();
+        OLD.save(&mut storage, &U64Key::new(id).joined_key(), &record(id))
+            .unwrap();
+    }
+    // entry of a record removed since
+    OLD.save(&mut storage, &U64Key::new(9).joined_key(), &record(9))
+        .unwrap();
+
+    let report = TO.verify_index(&storage, &NEW).unwrap();
+    assert_eq!(report.missing.len(), 5);
+    assert_eq!(report.orphaned.len(), 1);
+    assert_eq!(report.stale.len(), 5);
+
+    let mut cursor = None;
+    loop {
+        cursor = TO.rebuild_index(&mut storage, &NEW, cursor, 2).unwrap();
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert!(TO.verify_index(&storage, &NEW).unwrap().is_consistent());
+    assert_eq!(
+        NEW.prefix(11.into())
+            .keys(&storage, None, None, Order::Ascending)
+            .count(),
+        2
+    );
+}
+
+impl IndexListInspect<ToIndex> for ToIndexList<'_> {
+    fn get_inspects(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn IndexInspect<ToIndex>> + '_> {
+        let v: Vec<&dyn IndexInspect<ToIndex>> = vec![&self.count, &self.address];
+        Box::new(v.into_iter())
+    }
+}
+
+#[test]
+fn indexed_map_check_indexes() {
+    let mut storage = MockStorage::new();
+    const TO: IndexedMapCow<U64Key, ToIndex, ToIndexList> = IndexedMapCow::new_ref(
+        "check",
+        ToIndexList {
+            count: MultiIndexCow::new_ref("check", "check_count", |e, k| (e.count.into(), k)),
+            address: UniqueIndexCow::new_ref("check_address", |e| e.address.clone()),
+        },
+    );
+
+    let record = |id: u64, count: u64| ToIndex {
+        id,
+        count,
+        address: Addr::unchecked(format!("addr{}", id)),
+    };
+    for id in 0..3u64 {
+        TO.save(&mut storage, id.into(), &record(id, 1)).unwrap();
+    }
+    assert!(TO.check_indexes(&storage).unwrap().is_consistent());
+
+    let pk = |id: u64| U64Key::new(id).joined_key();
+    // record 0 written to primary map only, so both its index entries are outdated
+    TO.indexed_map()
+        .key(0.into())
+        .save(&mut storage, &record(0, 2))
+        .unwrap();
+    // index entries left behind by a record removed from primary map only
+    TO.indexed_map().key(2.into()).remove(&mut storage);
+
+    let report = TO.check_indexes(&storage).unwrap();
+    assert!(!report.is_consistent());
+    assert_eq!(
+        report.indexes,
+        vec![
+            IndexReport {
+                index: "check_count".to_string(),
+                missing: vec![Binary::from(pk(0))],
+                orphaned: vec![Binary::from(TO.index.count.index_key((1.into(), pk(2))))],
+                stale: vec![Binary::from(TO.index.count.index_key((1.into(), pk(0))))],
+            },
+            IndexReport {
+                index: "check_address".to_string(),
+                missing: vec![],
+                orphaned: vec![Binary::from(Addr::unchecked("addr2").joined_key())],
+                stale: vec![Binary::from(Addr::unchecked("addr0").joined_key())],
+            },
+        ]
+    );
+}
+
+#[test]
+fn index_prefix_de() {
+    let mut storage = MockStorage::new();
+    const POOL: IndexedMapCow<(&str, U64Key), ToIndex, ToIndexList> = IndexedMapCow::new_ref(
+        "typed",
+        ToIndexList {
+            count: MultiIndexCow::new_ref("typed", "typed_count", |e, k| (e.count.into(), k)),
+            address: UniqueIndexCow::new_ref("typed_address", |e| e.address.clone()),
+        },
+    );
+
+    for (pool, id, count) in [("a", 0u64, 5u64), ("b", 1, 5), ("a", 2, 7)] {
+        let record = ToIndex {
+            id,
+            count,
+            address: Addr::unchecked(format!("addr{}", id)),
+        };
+        POOL.save(&mut storage, (pool, id.into()), &record).unwrap();
+    }
+
+    assert_eq!(
+        POOL.index
+            .count
+            .prefix_de::<(&str, U64Key)>(5.into())
+            .keys(&storage, None, None, Order::Descending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap(),
+        vec![("b".to_string(), 1), ("a".to_string(), 0)]
+    );
+
+    let (pk, record) = POOL
+        .index
+        .address
+        .sub_prefix_de::<(&str, U64Key)>(())
+        .range(&storage, None, None, Order::Descending)
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(pk, ("a".to_string(), 2));
+    assert_eq!(record.count, 7);
+
+    // raw form is kept
+    assert_eq!(
+        POOL.index
+            .count
+            .prefix_de::<(&str, U64Key)>(7.into())
+            .range_raw(&storage, None, None, Order::Ascending)
+            .map(|e| e.unwrap().0)
+            .collect::<Vec<_>>(),
+        vec![("a", U64Key::new(2)).joined_key()]
+    );
+
+    // keys only read index entries, even when data is gone from the primary map
+    MapCow::<(&str, U64Key), ToIndex>::new_ref("typed").remove(&mut storage, ("b", 1.into()));
+    assert_eq!(
+        POOL.index
+            .count
+            .prefix_de::<(&str, U64Key)>(5.into())
+            .keys(&storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap(),
+        vec![("a".to_string(), 0), ("b".to_string(), 1)]
+    );
+    POOL.index
+        .count
+        .prefix_de::<(&str, U64Key)>(5.into())
+        .range(&storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()
+        .unwrap_err();
+    assert_eq!(
+        POOL.index
+            .address
+            .sub_prefix_de::<(&str, U64Key)>(())
+            .keys(&storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap(),
+        vec![
+            ("a".to_string(), 0),
+            ("b".to_string(), 1),
+            ("a".to_string(), 2)
+        ]
+    );
+}
+
+#[test]
+fn indexed_map_push() {
+    let mut storage = MockStorage::new();
+    let it = ItemMapAccessor::new("push");
+    const SEQ: SequenceCow = SequenceCow::new_ref("push_seq");
+    let record = |address: &str| ToIndex {
+        id: 0,
+        count: 1,
+        address: Addr::unchecked(address),
+    };
+
+    assert_eq!(
+        it.indexed_map
+            .push(&mut storage, &SEQ, &record("a"))
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        it.indexed_map
+            .push(&mut storage, &SEQ, &record("b"))
+            .unwrap(),
+        2
+    );
+
+    // failed save does not allocate an id
+    it.indexed_map
+        .push(&mut storage, &SEQ, &record("a"))
+        .unwrap_err();
+    assert_eq!(SEQ.current(&storage).unwrap(), 2);
+
+    assert_eq!(
+        it.indexed_map
+            .push(&mut storage, &SEQ, &record("c"))
+            .unwrap(),
+        3
+    );
+    assert_eq!(
+        it.indexed_map
+            .keys(&storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap(),
+        vec![1, 2, 3]
+    );
+}
@@ -0,0 +1,58 @@
+use cosmwasm_std::{Pair, StdResult, Storage};
+use serde::de::DeserializeOwned;
+
+use super::helpers::deserialize_multi_kv_custom_pk;
+
+/// Reversible transform of a raw primary key stored in an index key, e.g. to reverse its order.
+pub trait KeyTransform {
+    fn forward(pk: Vec<u8>) -> Vec<u8>;
+
+    fn inverse(key: Vec<u8>) -> Vec<u8>;
+}
+
+/// Invert every byte, reversing the order of fixed width keys, e.g. integer keys or addresses of
+/// the same length. `!x` of a big endian integer is `MAX - x`.
+pub struct Invert;
+
+impl KeyTransform for Invert {
+    fn forward(pk: Vec<u8>) -> Vec<u8> {
+        pk.into_iter().map(|b| !b).collect()
+    }
+
+    fn inverse(key: Vec<u8>) -> Vec<u8> {
+        Self::forward(key)
+    }
+}
+
+/// Deserializer of a multi index which pk is stored through `X::forward`.
+pub fn deserialize_multi_kv_transformed<T: DeserializeOwned, X: KeyTransform>(
+    store: &dyn Storage,
+    pk_namespace: &[u8],
+    kv: Pair,
+) -> StdResult<Pair<T>> {
+    deserialize_multi_kv_custom_pk(store, pk_namespace, kv, X::inverse)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cw_storage_plus::{PrimaryKey, U64Key};
+
+    #[test]
+    fn invert_reverses_order() {
+        let keys = [0u64, 1, 255, 256, u64::MAX]
+            .iter()
+            .map(|k| Invert::forward(U64Key::new(*k).joined_key()))
+            .collect::<Vec<_>>();
+
+        assert!(keys.windows(2).all(|w| w[0] > w[1]));
+        assert_eq!(
+            Invert::forward(U64Key::new(1).joined_key()),
+            U64Key::new(u64::MAX - 1).joined_key()
+        );
+        assert_eq!(
+            Invert::inverse(Invert::forward(b"addr".to_vec())),
+            b"addr".to_vec()
+        );
+    }
+}